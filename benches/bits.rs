@@ -0,0 +1,71 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Compares the internal slice-backed bit cursor with the io::Cursor +
+// bitstream-io path the decoder used before, on the fields of a v1 header.
+
+extern crate base64;
+extern crate bitstream_io;
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent;
+
+use std::io;
+
+use bitstream_io::{BigEndian, BitReader as IoBitReader};
+use criterion::{black_box, Criterion};
+
+use gdpr_consent::bits::BitReader;
+
+// version, created, last updated, CMP ID, CMP version, consent screen,
+// language, vendor list version, purposes, max vendor ID, encoding type
+const V1_HEADER: [u32; 13] = [6, 36, 36, 12, 12, 6, 6, 6, 12, 24, 16, 1, 1];
+
+fn consent_bytes() -> Vec<u8> {
+    base64::decode_config(
+        "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA",
+        base64::URL_SAFE_NO_PAD,
+    )
+    .unwrap()
+}
+
+fn read_header(data: &[u8]) -> u64 {
+    let mut reader = BitReader::new(data);
+    let mut sum = 0;
+    for bits in &V1_HEADER {
+        sum += reader.read(*bits).unwrap();
+    }
+    sum
+}
+
+fn read_header_bitstream_io(data: &[u8]) -> u64 {
+    let mut cursor = io::Cursor::new(data);
+    let mut reader = IoBitReader::endian(&mut cursor, BigEndian);
+    let mut sum = 0;
+    for bits in &V1_HEADER {
+        sum += reader.read::<u64>(*bits).unwrap();
+    }
+    sum
+}
+
+fn bench_read(c: &mut Criterion) {
+    let data = consent_bytes();
+    assert_eq!(read_header(&data), read_header_bitstream_io(&data));
+
+    let mut group = c.benchmark_group("v1 header");
+    group.bench_function("bits::BitReader", |b| {
+        b.iter(|| read_header(black_box(&data)))
+    });
+    group.bench_function("bitstream_io::BitReader", |b| {
+        b.iter(|| read_header_bitstream_io(black_box(&data)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_read);
+criterion_main!(benches);
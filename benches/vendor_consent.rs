@@ -0,0 +1,98 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Parse and serialize benchmarks for consent strings of each vendor encoding.
+// Accepted timings are recorded in benches/README.md.
+
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent;
+
+use std::time::Duration;
+
+use criterion::{black_box, Criterion};
+
+use gdpr_consent::ids::{PurposeId, VendorId};
+use gdpr_consent::vendor_consent::{VendorConsent, V1};
+
+fn v1(max_vendor_id: u16, consented: fn(u16) -> bool) -> VendorConsent {
+    VendorConsent::V1(V1 {
+        created: "2017-11-07T19:15:55.4Z".parse().unwrap(),
+        last_updated: "2018-05-11T12:00:00.0Z".parse().unwrap(),
+        cmp_id: 7,
+        cmp_version: 1,
+        consent_screen: 3,
+        consent_language: "en".to_string(),
+        vendor_list_version: 8,
+        purposes_allowed: (1..=5).map(PurposeId).collect(),
+        max_vendor_id: max_vendor_id as usize,
+        vendor_consent: (1..=max_vendor_id)
+            .filter(|id| consented(*id))
+            .map(VendorId)
+            .collect(),
+    })
+}
+
+// (name, consent string)
+fn fixtures() -> Vec<(&'static str, String)> {
+    vec![
+        // single vendor
+        ("tiny", v1(1, |_| true).to_string().unwrap()),
+        // every other vendor consented, so ranges are longer than a bitfield
+        (
+            "bitfield",
+            v1(600, |id| id.is_multiple_of(2)).to_string().unwrap(),
+        ),
+        // all vendors but one consented
+        ("range", "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".to_string()),
+        // the largest vendor ID a consent string can hold, bitfield encoded
+        (
+            "max_bitfield",
+            v1(65535, |id| id.is_multiple_of(2)).to_string().unwrap(),
+        ),
+        // the largest vendor ID a consent string can hold, range encoded
+        (
+            "max_range",
+            v1(65535, |id| !id.is_multiple_of(100)).to_string().unwrap(),
+        ),
+        (
+            "v2",
+            "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA".to_string(),
+        ),
+    ]
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("VendorConsent::from_str");
+    for (name, s) in fixtures() {
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&s).parse::<VendorConsent>().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("VendorConsent::to_string");
+    for (name, s) in fixtures() {
+        let consent: VendorConsent = s.parse().unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&consent).to_string().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .measurement_time(Duration::from_secs(3))
+        .noise_threshold(0.05);
+    targets = bench_parse, bench_serialize
+}
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Global vendor list parse benchmarks, from JSON and from binary snapshots.
+// Accepted timings are recorded in benches/README.md.
+
+#[macro_use]
+extern crate criterion;
+extern crate gdpr_consent;
+
+use std::time::Duration;
+
+use criterion::{black_box, Criterion};
+
+#[cfg(feature = "snapshot")]
+use gdpr_consent::snapshot::{self, StripOptions};
+use gdpr_consent::vendor_list::VendorList;
+
+const PURPOSES: [&str; 10] = [
+    "Store and/or access information on a device",
+    "Select basic ads",
+    "Create a personalised ads profile",
+    "Select personalised ads",
+    "Create a personalised content profile",
+    "Select personalised content",
+    "Measure ad performance",
+    "Measure content performance",
+    "Apply market research to generate audience insights",
+    "Develop and improve products",
+];
+
+const FEATURES: [&str; 3] = [
+    "Match and combine offline data sources",
+    "Link different devices",
+    "Receive and use automatically-sent device characteristics for identification",
+];
+
+fn ids(ids: &[u8]) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    format!("[{}]", ids.join(","))
+}
+
+// A v2 global vendor list shaped like the published ones (GVL v48 has ~600
+// vendors), including the fields this crate does not read.
+fn gvl_fixture() -> String {
+    let purposes: Vec<String> = PURPOSES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            format!(
+                r#""{id}":{{"id":{id},"name":"{name}","description":"{name}.","descriptionLegal":"Vendors can: {name}."}}"#,
+                id = i + 1,
+                name = name
+            )
+        })
+        .collect();
+
+    let features: Vec<String> = FEATURES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            format!(
+                r#""{id}":{{"id":{id},"name":"{name}","description":"{name}.","descriptionLegal":"Vendors can: {name}."}}"#,
+                id = i + 1,
+                name = name
+            )
+        })
+        .collect();
+
+    let vendors: Vec<String> = (1..=650)
+        .map(|id| {
+            let consent: Vec<u8> = (1..=10).filter(|p| !(id + *p as usize).is_multiple_of(3)).collect();
+            let leg_int: Vec<u8> = (2..=10).filter(|p| !consent.contains(p)).collect();
+            format!(
+                r#""{id}":{{"id":{id},"name":"Vendor {id} Ltd","purposes":{purposes},"legIntPurposes":{leg_int},"flexiblePurposes":{flexible},"specialPurposes":[1,2],"features":{features},"specialFeatures":[],"policyUrl":"https://vendor{id}.example.com/privacy","cookieMaxAgeSeconds":31536000,"usesCookies":true,"cookieRefresh":false,"usesNonCookieAccess":true}}"#,
+                id = id,
+                purposes = ids(&consent),
+                leg_int = ids(&leg_int),
+                flexible = ids(&leg_int[..leg_int.len() / 2]),
+                features = ids(&[1, 2, 3][..id % 4]),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"gvlSpecificationVersion":2,"vendorListVersion":48,"tcfPolicyVersion":2,"lastUpdated":"2020-07-02T16:00:23Z","purposes":{{{}}},"specialPurposes":{{}},"features":{{{}}},"specialFeatures":{{}},"stacks":{{}},"vendors":{{{}}}}}"#,
+        purposes.join(","),
+        features.join(","),
+        vendors.join(",")
+    )
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let gvl = gvl_fixture();
+    assert_eq!(gvl.parse::<VendorList>().unwrap().vendors.len(), 650);
+
+    c.bench_function("VendorList::from_str/v2", |b| {
+        b.iter(|| black_box(&gvl).parse::<VendorList>().unwrap())
+    });
+}
+
+#[cfg(feature = "snapshot")]
+fn bench_snapshot(c: &mut Criterion) {
+    let gvl = gvl_fixture();
+    let mut group = c.benchmark_group("snapshot::decode");
+    for (name, strip) in &[
+        ("v2", StripOptions::default()),
+        ("v2_stripped", StripOptions::all()),
+    ] {
+        let snapshot = snapshot::from_json(&gvl, strip).unwrap();
+        group.bench_function(*name, |b| {
+            b.iter(|| snapshot::decode(black_box(&snapshot)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "snapshot"))]
+fn bench_snapshot(_: &mut Criterion) {}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .measurement_time(Duration::from_secs(5))
+        .noise_threshold(0.05);
+    targets = bench_parse, bench_snapshot
+}
+criterion_main!(benches);
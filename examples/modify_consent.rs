@@ -6,18 +6,20 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-extern crate chrono;
 extern crate gdpr_consent;
 
+use gdpr_consent::ids::VendorId;
 use gdpr_consent::vendor_consent::VendorConsent;
 use std::error::Error;
 
-fn main() -> Result<(), Box<Error>> {
-    let vendor_consent = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".parse()?;
-    let VendorConsent::V1(mut v1) = vendor_consent;
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut v1 = match "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".parse()? {
+        VendorConsent::V1(v1) => v1,
+        _ => return Err("Expected a v1 consent string".into()),
+    };
 
     v1.last_updated = "2018-05-11T12:00:00.000Z".parse()?;
-    v1.vendor_consent.remove(9); // remove consent for Vendor ID 10
+    v1.vendor_consent.remove(VendorId(10));
 
     let serialized = VendorConsent::V1(v1).to_string()?;
     assert_eq!(serialized, "BOEFEAyONlzmAAHABDENAI4AAAB9vABgASABQA");
@@ -12,7 +12,7 @@ extern crate reqwest;
 use gdpr_consent::vendor_list::VendorList;
 use std::error::Error;
 
-fn main() -> Result<(), Box<Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     let json = reqwest::get("https://vendorlist.consensu.org/vendorlist.json")?.text()?;
     let vendor_list: VendorList = json.parse()?;
 
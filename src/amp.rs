@@ -0,0 +1,502 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Payloads exchanged with AMP's amp-consent: the request it POSTs to a CMP's
+// checkConsentHref endpoint, the endpoint's response, and the consent
+// metadata and state it passes on to vendors.
+
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde;
+use serde_json::{self, Map, Value};
+
+use ids::PurposeId;
+use vendor_consent::{self, VendorConsent};
+
+// How the consent string is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentStringType {
+    TcfV1,
+    TcfV2,
+    UsPrivacy,
+    Gpp,
+    // A type this crate does not know of
+    Other(u8),
+}
+
+impl ConsentStringType {
+    pub fn as_u8(self) -> u8 {
+        match self {
+            ConsentStringType::TcfV1 => 1,
+            ConsentStringType::TcfV2 => 2,
+            ConsentStringType::UsPrivacy => 3,
+            ConsentStringType::Gpp => 4,
+            ConsentStringType::Other(t) => t,
+        }
+    }
+}
+
+impl From<u8> for ConsentStringType {
+    fn from(t: u8) -> ConsentStringType {
+        match t {
+            1 => ConsentStringType::TcfV1,
+            2 => ConsentStringType::TcfV2,
+            3 => ConsentStringType::UsPrivacy,
+            4 => ConsentStringType::Gpp,
+            t => ConsentStringType::Other(t),
+        }
+    }
+}
+
+impl serde::Serialize for ConsentStringType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.as_u8())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ConsentStringType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u8::deserialize(deserializer).map(ConsentStringType::from)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConsentMetadata {
+    #[serde(
+        rename = "consentStringType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_string_type: Option<ConsentStringType>,
+
+    #[serde(
+        rename = "gdprApplies",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub gdpr_applies: Option<bool>,
+
+    // Google's Additional Consent string
+    #[serde(
+        rename = "additionalConsent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_consent: Option<String>,
+
+    // Whether the user consented to purpose 1, for TCF v2 strings
+    #[serde(
+        rename = "purposeOne",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub purpose_one: Option<bool>,
+}
+
+// The user's decision as stored by amp-consent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentState {
+    #[serde(rename = "accepted")]
+    Accepted,
+    #[serde(rename = "rejected")]
+    Rejected,
+    #[serde(rename = "unknown")]
+    Unknown,
+}
+
+// The consent state vendors see through the CONSENT_STATE macro
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentPolicyState {
+    Sufficient,
+    Insufficient,
+    UnknownNotRequired,
+    Unknown,
+}
+
+impl ConsentPolicyState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConsentPolicyState::Sufficient => "sufficient",
+            ConsentPolicyState::Insufficient => "insufficient",
+            ConsentPolicyState::UnknownNotRequired => "unknown_not_required",
+            ConsentPolicyState::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<ConsentState> for ConsentPolicyState {
+    fn from(state: ConsentState) -> ConsentPolicyState {
+        match state {
+            ConsentState::Accepted => ConsentPolicyState::Sufficient,
+            ConsentState::Rejected => ConsentPolicyState::Insufficient,
+            ConsentState::Unknown => ConsentPolicyState::Unknown,
+        }
+    }
+}
+
+impl FromStr for ConsentPolicyState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sufficient" => Ok(ConsentPolicyState::Sufficient),
+            "insufficient" => Ok(ConsentPolicyState::Insufficient),
+            "unknown_not_required" => Ok(ConsentPolicyState::UnknownNotRequired),
+            "unknown" => Ok(ConsentPolicyState::Unknown),
+            _ => Err(Error::Other(format!("Unknown AMP consent state '{}'", s))),
+        }
+    }
+}
+
+impl Display for ConsentPolicyState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+// Body amp-consent POSTs to the checkConsentHref endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConsentRequest {
+    #[serde(rename = "consentInstanceId", default)]
+    pub consent_instance_id: String,
+
+    // Previously stored state, if any
+    #[serde(
+        rename = "consentStateValue",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_state: Option<ConsentState>,
+
+    #[serde(
+        rename = "consentString",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_string: Option<String>,
+
+    #[serde(
+        rename = "consentMetadata",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_metadata: Option<ConsentMetadata>,
+
+    // Whether the stored consent has been marked for re-prompting
+    #[serde(rename = "isDirty", default, skip_serializing_if = "Option::is_none")]
+    pub is_dirty: Option<bool>,
+
+    #[serde(
+        rename = "matchedGeoGroup",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub matched_geo_group: Option<String>,
+
+    // Fields this crate does not model
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ConsentRequest {
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+
+    // Decodes the stored consent string, if any.
+    pub fn decode(&self) -> Result<Option<VendorConsent>, Error> {
+        decode(
+            self.consent_string.as_deref(),
+            self.consent_metadata.as_ref(),
+        )
+    }
+}
+
+impl FromStr for ConsentRequest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(From::from)
+    }
+}
+
+// checkConsentHref endpoint response
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConsentResponse {
+    // Whether the user must be asked for consent at all
+    #[serde(rename = "consentRequired")]
+    pub consent_required: bool,
+
+    #[serde(
+        rename = "consentStateValue",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_state: Option<ConsentState>,
+
+    #[serde(
+        rename = "consentString",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_string: Option<String>,
+
+    #[serde(
+        rename = "consentMetadata",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_metadata: Option<ConsentMetadata>,
+
+    // Discard consent cached on the client
+    #[serde(
+        rename = "expireCache",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expire_cache: Option<bool>,
+
+    // Passed to the CMP's prompt UI as is
+    #[serde(
+        rename = "sharedData",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub shared_data: Option<Map<String, Value>>,
+}
+
+impl ConsentResponse {
+    // Response carrying a TCF consent string with its metadata.
+    pub fn for_consent(
+        consent: &VendorConsent,
+        state: ConsentState,
+        gdpr_applies: bool,
+    ) -> Result<ConsentResponse, Error> {
+        Ok(ConsentResponse {
+            consent_required: gdpr_applies,
+            consent_state: Some(state),
+            consent_string: Some(consent.to_string()?),
+            consent_metadata: Some(metadata_for(consent, gdpr_applies)),
+            ..Default::default()
+        })
+    }
+
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+
+    pub fn decode(&self) -> Result<Option<VendorConsent>, Error> {
+        decode(
+            self.consent_string.as_deref(),
+            self.consent_metadata.as_ref(),
+        )
+    }
+
+    // What vendors see through the CONSENT_STATE macro
+    pub fn policy_state(&self) -> ConsentPolicyState {
+        match self.consent_state {
+            _ if !self.consent_required => ConsentPolicyState::UnknownNotRequired,
+            Some(state) => state.into(),
+            None => ConsentPolicyState::Unknown,
+        }
+    }
+}
+
+impl FromStr for ConsentResponse {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(From::from)
+    }
+}
+
+pub fn metadata_for(consent: &VendorConsent, gdpr_applies: bool) -> ConsentMetadata {
+    let (consent_string_type, purpose_one) = match consent {
+        VendorConsent::V1(_) => (ConsentStringType::TcfV1, None),
+        VendorConsent::V2(_) => (
+            ConsentStringType::TcfV2,
+            Some(consent.has_purpose_consent(PurposeId(1))),
+        ),
+    };
+    ConsentMetadata {
+        consent_string_type: Some(consent_string_type),
+        gdpr_applies: Some(gdpr_applies),
+        additional_consent: None,
+        purpose_one,
+    }
+}
+
+// Decodes a consent string according to its declared type. Strings without a
+// declared type are decoded as whichever TCF version they hold.
+pub fn decode(
+    consent_string: Option<&str>,
+    metadata: Option<&ConsentMetadata>,
+) -> Result<Option<VendorConsent>, Error> {
+    let s = match consent_string {
+        Some(s) if !s.is_empty() => s,
+        _ => return Ok(None),
+    };
+
+    let declared = metadata.and_then(|m| m.consent_string_type);
+    let consent: VendorConsent = match declared {
+        None | Some(ConsentStringType::TcfV1) | Some(ConsentStringType::TcfV2) => s.parse()?,
+        Some(t) => return Err(Error::UnsupportedConsentStringType(t)),
+    };
+
+    let actual = match consent {
+        VendorConsent::V1(_) => ConsentStringType::TcfV1,
+        VendorConsent::V2(_) => ConsentStringType::TcfV2,
+    };
+    if declared.is_some_and(|t| t != actual) {
+        return Err(Error::Other(format!(
+            "Consent string type {} does not match the TCF version of the consent string",
+            declared.map_or(0, ConsentStringType::as_u8)
+        )));
+    }
+    Ok(Some(consent))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    VendorConsentError(vendor_consent::Error),
+    // US Privacy and GPP strings have no decoder in this crate
+    UnsupportedConsentStringType(ConsentStringType),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::JsonError(ref err) => Some(err),
+            Error::VendorConsentError(ref err) => Some(err),
+            Error::UnsupportedConsentStringType(_) | Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::JsonError(ref err) => Display::fmt(err, f),
+            Error::VendorConsentError(ref err) => Display::fmt(err, f),
+            Error::UnsupportedConsentStringType(t) => {
+                write!(f, "Unsupported consent string type {}", t.as_u8())
+            }
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+impl From<vendor_consent::Error> for Error {
+    fn from(e: vendor_consent::Error) -> Error {
+        Error::VendorConsentError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_fixtures::V2_CONSENT;
+
+    #[test]
+    fn parse_request() {
+        let request: ConsentRequest = r#"{
+            "consentInstanceId": "ABC",
+            "consentStateValue": "accepted",
+            "consentString": "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA",
+            "consentMetadata": { "consentStringType": 2, "gdprApplies": true, "purposeOne": true },
+            "isDirty": false,
+            "matchedGeoGroup": "eea",
+            "purposeConsents": { "p1": "accepted" }
+        }"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(request.consent_state, Some(ConsentState::Accepted));
+        assert_eq!(request.matched_geo_group.as_ref().unwrap(), "eea");
+        assert!(request.extra.contains_key("purposeConsents"));
+        match request.decode().unwrap() {
+            Some(VendorConsent::V2(v2)) => assert_eq!(v2.cmp_id, 27),
+            other => panic!("expected a v2 consent string, got {:?}", other),
+        }
+        assert_eq!(
+            request
+                .to_string()
+                .unwrap()
+                .parse::<ConsentRequest>()
+                .unwrap(),
+            request
+        );
+
+        let empty: ConsentRequest = r#"{"consentInstanceId":"ABC"}"#.parse().unwrap();
+        assert!(empty.decode().unwrap().is_none());
+    }
+
+    #[test]
+    fn produce_response() {
+        let consent: VendorConsent = V2_CONSENT.parse().unwrap();
+        let response =
+            ConsentResponse::for_consent(&consent, ConsentState::Accepted, true).unwrap();
+        assert_eq!(
+            response.to_string().unwrap(),
+            format!(
+                concat!(
+                    r#"{{"consentRequired":true,"consentStateValue":"accepted","consentString":"{}","#,
+                    r#""consentMetadata":{{"consentStringType":2,"gdprApplies":true,"purposeOne":{}}}}}"#
+                ),
+                V2_CONSENT,
+                consent.has_purpose_consent(1)
+            )
+        );
+        assert_eq!(response.policy_state(), ConsentPolicyState::Sufficient);
+        assert_eq!(response.policy_state().to_string(), "sufficient");
+
+        let not_required: ConsentResponse = r#"{"consentRequired":false}"#.parse().unwrap();
+        assert_eq!(
+            not_required.policy_state(),
+            ConsentPolicyState::UnknownNotRequired
+        );
+    }
+
+    #[test]
+    fn decode_by_type() {
+        let metadata = |t: u8| ConsentMetadata {
+            consent_string_type: Some(t.into()),
+            ..Default::default()
+        };
+        assert!(decode(Some(V2_CONSENT), Some(&metadata(2)))
+            .unwrap()
+            .is_some());
+        assert!(decode(Some(V2_CONSENT), None).unwrap().is_some());
+        // declared v1 but holds v2
+        assert!(decode(Some(V2_CONSENT), Some(&metadata(1))).is_err());
+
+        for (t, s) in &[
+            (3, "1YNN"),
+            (4, "DBABMA~CPXxRfAPXxRfAAfKABENB-CgAAAAAAAAAAYgAAAAAAAA"),
+        ] {
+            match decode(Some(s), Some(&metadata(*t))) {
+                Err(Error::UnsupportedConsentStringType(unsupported)) => {
+                    assert_eq!(unsupported.as_u8(), *t)
+                }
+                other => panic!("expected an unsupported type, got {:?}", other),
+            }
+        }
+    }
+}
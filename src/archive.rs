@@ -0,0 +1,431 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+
+#[cfg(feature = "integrity")]
+use integrity::{self, IntegrityOptions};
+use vendor_consent::VendorConsent;
+use vendor_list::{self, VendorList};
+
+// Historical vendor lists, one file per version, as published by the IAB:
+// vendorlist-v{N}.json, optionally gzip-compressed as vendorlist-v{N}.json.gz.
+//
+// v1 and v2 vendor list versions overlap, so an archive holds the lists of a
+// single TCF version. Lists are parsed the first time they are requested.
+//
+// Detached signatures, when checked, are read from {file name}.sig and cover
+// the canonical JSON form of the list.
+#[derive(Debug)]
+pub struct VendorListArchive {
+    dir: PathBuf,
+    paths: BTreeMap<u16, PathBuf>,
+    #[cfg(feature = "integrity")]
+    integrity: IntegrityOptions,
+    loaded: Mutex<HashMap<u16, Arc<VendorList>>>,
+}
+
+impl VendorListArchive {
+    // Finds the vendor lists in the directory without parsing them.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<VendorListArchive, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut paths = BTreeMap::new();
+
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let version = match path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(parse_file_name)
+            {
+                Some(version) => version,
+                None => continue,
+            };
+            if let Some(other) = paths.insert(version, path.clone()) {
+                return Err(Error::Other(format!(
+                    "Vendor list version {} is in both {} and {}",
+                    version,
+                    other.display(),
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(VendorListArchive {
+            dir,
+            paths,
+            #[cfg(feature = "integrity")]
+            integrity: IntegrityOptions::default(),
+            loaded: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // Like open(), checking each list against the manifest and signature key
+    // as it is loaded.
+    #[cfg(feature = "integrity")]
+    pub fn open_with<P: AsRef<Path>>(
+        dir: P,
+        integrity: IntegrityOptions,
+    ) -> Result<VendorListArchive, Error> {
+        let mut archive = VendorListArchive::open(dir)?;
+        archive.integrity = integrity;
+        Ok(archive)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Versions in the archive in ascending order
+    pub fn versions(&self) -> impl Iterator<Item = u16> + '_ {
+        self.paths.keys().cloned()
+    }
+
+    // None if the version is not in the archive.
+    pub fn get(&self, version: u16) -> Result<Option<Arc<VendorList>>, Error> {
+        let path = match self.paths.get(&version) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(vendor_list) = loaded.get(&version) {
+            return Ok(Some(vendor_list.clone()));
+        }
+
+        let vendor_list = Arc::new(self.load(path)?);
+        if vendor_list.version != version {
+            return Err(Error::Other(format!(
+                "{} holds vendor list version {}",
+                path.display(),
+                vendor_list.version
+            )));
+        }
+        loaded.insert(version, vendor_list.clone());
+        Ok(Some(vendor_list))
+    }
+
+    pub fn latest(&self) -> Result<Option<Arc<VendorList>>, Error> {
+        match self.paths.keys().next_back() {
+            Some(&version) => self.get(version),
+            None => Ok(None),
+        }
+    }
+
+    // The vendor list in effect at the given time: the latest version last
+    // updated at or before it. None if every list in the archive is newer.
+    pub fn at(&self, t: DateTime<Utc>) -> Result<Option<Arc<VendorList>>, Error> {
+        // lists are published in version order, so only the lists visited by
+        // a binary search need to be parsed
+        let versions: Vec<u16> = self.versions().collect();
+        let (mut lo, mut hi) = (0, versions.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self
+                .get(versions[mid])?
+                .is_some_and(|l| l.last_updated <= t)
+            {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        match lo.checked_sub(1) {
+            Some(i) => self.get(versions[i]),
+            None => Ok(None),
+        }
+    }
+
+    // The vendor list the consent string was collected against.
+    pub fn for_consent(&self, consent: &VendorConsent) -> Result<Option<Arc<VendorList>>, Error> {
+        self.get(consent.vendor_list_version())
+    }
+
+    fn load(&self, path: &Path) -> Result<VendorList, Error> {
+        let data = fs::read(path)?;
+        #[cfg(feature = "integrity")]
+        self.integrity.verify_file(file_name(path), &data)?;
+
+        let json = if path.extension().is_some_and(|ext| ext == "gz") {
+            let mut json = Vec::new();
+            GzDecoder::new(data.as_slice()).read_to_end(&mut json)?;
+            json
+        } else {
+            data
+        };
+
+        let json = String::from_utf8(json)
+            .map_err(|e| Error::Other(format!("{} is not UTF-8: {}", path.display(), e)))?;
+        let vendor_list: VendorList = json.parse()?;
+
+        #[cfg(feature = "integrity")]
+        self.verify_signature(path, &vendor_list)?;
+        Ok(vendor_list)
+    }
+
+    #[cfg(feature = "integrity")]
+    fn verify_signature(&self, path: &Path, vendor_list: &VendorList) -> Result<(), Error> {
+        let name = file_name(path);
+        let signature = match fs::read_to_string(path.with_file_name(format!("{}.sig", name))) {
+            Ok(signature) => Some(signature),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        self.integrity
+            .verify_signature(name, vendor_list, signature.as_deref())
+            .map_err(From::from)
+    }
+}
+
+// Archives are the same if they hold the same files with the same checks.
+impl PartialEq for VendorListArchive {
+    fn eq(&self, other: &VendorListArchive) -> bool {
+        #[cfg(feature = "integrity")]
+        {
+            if self.integrity != other.integrity {
+                return false;
+            }
+        }
+        self.paths == other.paths
+    }
+}
+
+#[cfg(feature = "integrity")]
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
+
+// "vendorlist-v{N}.json" or "vendorlist-v{N}.json.gz"
+fn parse_file_name(name: &str) -> Option<u16> {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    name.strip_prefix("vendorlist-v")?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(io::Error),
+    VendorListError(vendor_list::Error),
+    #[cfg(feature = "integrity")]
+    IntegrityError(integrity::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::IoError(ref err) => Some(err),
+            Error::VendorListError(ref err) => Some(err),
+            #[cfg(feature = "integrity")]
+            Error::IntegrityError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IoError(ref err) => Display::fmt(err, f),
+            Error::VendorListError(ref err) => Display::fmt(err, f),
+            #[cfg(feature = "integrity")]
+            Error::IntegrityError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::IoError(e)
+    }
+}
+
+impl From<vendor_list::Error> for Error {
+    fn from(e: vendor_list::Error) -> Error {
+        Error::VendorListError(e)
+    }
+}
+
+#[cfg(feature = "integrity")]
+impl From<integrity::Error> for Error {
+    fn from(e: integrity::Error) -> Error {
+        Error::IntegrityError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[cfg(feature = "integrity")]
+    use integrity::{Manifest, SigningKey};
+    use vendor_consent::ValidationOptions;
+
+    fn vendor_list(version: u16, last_updated: &str) -> String {
+        format!(
+            r#"{{
+                "vendorListVersion": {},
+                "lastUpdated": "{}",
+                "purposes": [],
+                "features": [],
+                "vendors": [
+                    {{ "id": 1, "name": "Example Vendor", "policyUrl": "", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] }}
+                ]
+            }}"#,
+            version, last_updated
+        )
+    }
+
+    // Versions 6-8 of the v1 vendor list, version 7 compressed
+    fn archive_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gdpr_consent-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("vendorlist-v6.json"),
+            vendor_list(6, "2017-10-01T16:00:00Z"),
+        )
+        .unwrap();
+        let mut gz = GzEncoder::new(
+            File::create(dir.join("vendorlist-v7.json.gz")).unwrap(),
+            Compression::default(),
+        );
+        gz.write_all(vendor_list(7, "2017-11-01T16:00:00Z").as_bytes())
+            .unwrap();
+        gz.finish().unwrap();
+        fs::write(
+            dir.join("vendorlist-v8.json"),
+            vendor_list(8, "2017-11-07T16:00:00Z"),
+        )
+        .unwrap();
+        fs::write(dir.join("README"), "not a vendor list").unwrap();
+        dir
+    }
+
+    #[test]
+    fn open_and_lookup() {
+        let dir = archive_dir("lookup");
+        let archive = VendorListArchive::open(&dir).unwrap();
+        assert_eq!(archive.versions().collect::<Vec<_>>(), vec![6, 7, 8]);
+
+        assert_eq!(archive.get(7).unwrap().unwrap().version, 7);
+        assert!(archive.get(9).unwrap().is_none());
+        assert_eq!(archive.latest().unwrap().unwrap().version, 8);
+        assert!(Arc::ptr_eq(
+            &archive.get(8).unwrap().unwrap(),
+            &archive.latest().unwrap().unwrap()
+        ));
+
+        let at = |t: &str| archive.at(t.parse().unwrap()).unwrap().map(|l| l.version);
+        assert_eq!(at("2017-09-01T00:00:00Z"), None);
+        assert_eq!(at("2017-11-01T16:00:00Z"), Some(7));
+        assert_eq!(at("2017-11-07T15:59:59Z"), Some(7));
+        assert_eq!(at("2018-05-01T00:00:00Z"), Some(8));
+
+        fs::write(dir.join("vendorlist-v8.json.gz"), "").unwrap();
+        assert!(VendorListArchive::open(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn validate_vendor_list_version() {
+        let dir = archive_dir("validate");
+        // created 2017-11-07T19:15:55.4Z against vendor list version 8
+        let s = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+        let consent: VendorConsent = s.parse().unwrap();
+
+        let mut options = ValidationOptions {
+            vendor_list_archive: Some(Arc::new(VendorListArchive::open(&dir).unwrap())),
+            ..Default::default()
+        };
+        assert!(VendorConsent::parse_with(s, &options).is_ok());
+        let archive = options.vendor_list_archive.as_ref().unwrap();
+        assert_eq!(archive.for_consent(&consent).unwrap().unwrap().version, 8);
+
+        // published after the consent string was last updated
+        fs::write(
+            dir.join("vendorlist-v8.json"),
+            vendor_list(8, "2018-01-01T00:00:00Z"),
+        )
+        .unwrap();
+        options.vendor_list_archive = Some(Arc::new(VendorListArchive::open(&dir).unwrap()));
+        assert!(VendorConsent::parse_with(s, &options).is_err());
+
+        fs::remove_file(dir.join("vendorlist-v8.json")).unwrap();
+        options.vendor_list_archive = Some(Arc::new(VendorListArchive::open(&dir).unwrap()));
+        assert!(VendorConsent::parse_with(s, &options).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "integrity")]
+    fn verify_integrity() {
+        let dir = archive_dir("integrity");
+        let mut manifest = Manifest::default();
+        for name in &["vendorlist-v6.json", "vendorlist-v7.json.gz"] {
+            manifest.insert(name, &fs::read(dir.join(name)).unwrap());
+        }
+        let key = SigningKey::from_bytes(&integrity::sha256(b"archive test key"));
+        for (name, version, last_updated) in &[
+            ("vendorlist-v6.json", 6, "2017-10-01T16:00:00Z"),
+            ("vendorlist-v7.json.gz", 7, "2017-11-01T16:00:00Z"),
+        ] {
+            let signature =
+                integrity::sign(&vendor_list(*version, last_updated).parse().unwrap(), &key)
+                    .unwrap();
+            fs::write(
+                dir.join(format!("{}.sig", name)),
+                integrity::encode_signature(&signature),
+            )
+            .unwrap();
+        }
+
+        let options = IntegrityOptions {
+            manifest: Some(manifest),
+            public_key: Some(key.verifying_key()),
+        };
+        let archive = VendorListArchive::open_with(&dir, options.clone()).unwrap();
+        assert_eq!(archive.get(6).unwrap().unwrap().version, 6);
+        assert_eq!(archive.get(7).unwrap().unwrap().version, 7);
+        // neither in the manifest nor signed
+        assert!(archive.get(8).is_err());
+
+        fs::write(
+            dir.join("vendorlist-v6.json"),
+            vendor_list(6, "2017-10-02T16:00:00Z"),
+        )
+        .unwrap();
+        let archive = VendorListArchive::open_with(&dir, options).unwrap();
+        assert!(archive.get(6).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Builds deployable artifacts from global vendor lists.
+
+extern crate gdpr_consent;
+
+use std::env;
+use std::error::Error;
+#[cfg(any(feature = "snapshot", feature = "integrity"))]
+use std::fs;
+#[cfg(feature = "integrity")]
+use std::path::Path;
+use std::process;
+
+#[cfg(feature = "integrity")]
+use gdpr_consent::integrity::{self, Manifest};
+#[cfg(feature = "snapshot")]
+use gdpr_consent::snapshot::{self, StripOptions};
+#[cfg(feature = "integrity")]
+use gdpr_consent::vendor_list::VendorList;
+
+const USAGE: &str = "\
+Usage:
+  gvl snapshot [options] <vendorlist.json> <output>
+  gvl sign --key <key file> <vendorlist.json> [<signature>]
+
+snapshot: converts a vendor list to a binary snapshot.
+
+  --strip-policy-urls   leave out vendor policy URLs
+  --strip-descriptions  leave out purpose, feature and stack descriptions
+  --strip-extra         leave out fields the library does not model
+  --strip-all           all of the above
+
+sign: writes a detached ed25519 signature of the vendor list, by default to
+<vendorlist.json>.sig, and prints the list's manifest line. The key file holds
+a base64 encoded 32 byte secret key.
+
+snapshot needs the snapshot feature, sign the integrity feature.";
+
+#[cfg(feature = "snapshot")]
+fn snapshot(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut strip = StripOptions::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--strip-policy-urls" => strip.policy_urls = true,
+            "--strip-descriptions" => strip.descriptions = true,
+            "--strip-extra" => strip.extra = true,
+            "--strip-all" => strip = StripOptions::all(),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag).into()),
+            path => paths.push(path),
+        }
+    }
+    let (input, output) = match paths.as_slice() {
+        [input, output] => (input, output),
+        _ => return Err(USAGE.into()),
+    };
+
+    let json = fs::read_to_string(input)?;
+    let snapshot = snapshot::from_json(&json, &strip)?;
+    fs::write(output, &snapshot)?;
+    eprintln!(
+        "Wrote {} ({} bytes, {} bytes of JSON)",
+        output,
+        snapshot.len(),
+        json.len()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "integrity")]
+fn sign(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (key_path, paths) = match args {
+        [flag, key_path, paths @ ..] if flag == "--key" => (key_path, paths),
+        _ => return Err(USAGE.into()),
+    };
+    let (input, output) = match paths {
+        [input] => (input, format!("{}.sig", input)),
+        [input, output] => (input, output.clone()),
+        _ => return Err(USAGE.into()),
+    };
+
+    let key = integrity::decode_signing_key(&fs::read_to_string(key_path)?)?;
+    let data = fs::read(input)?;
+    let vendor_list: VendorList = String::from_utf8(data.clone())?.parse()?;
+    let signature = integrity::sign(&vendor_list, &key)?;
+    fs::write(&output, integrity::encode_signature(&signature))?;
+
+    let mut manifest = Manifest::default();
+    let name = Path::new(input).file_name().and_then(|n| n.to_str());
+    manifest.insert(name.unwrap_or(input), &data);
+    print!("{}", manifest);
+    eprintln!(
+        "Wrote {}, verify with public key {}",
+        output,
+        integrity::encode_verifying_key(&key.verifying_key())
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result: Result<(), Box<dyn Error>> = match args.first().map(String::as_str) {
+        #[cfg(feature = "snapshot")]
+        Some("snapshot") => snapshot(&args[1..]),
+        #[cfg(not(feature = "snapshot"))]
+        Some("snapshot") => Err("gvl was built without the snapshot feature".into()),
+        #[cfg(feature = "integrity")]
+        Some("sign") => sign(&args[1..]),
+        #[cfg(not(feature = "integrity"))]
+        Some("sign") => Err("gvl was built without the integrity feature".into()),
+        _ => Err(USAGE.into()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
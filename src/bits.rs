@@ -0,0 +1,334 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem;
+
+use vendor_consent::Error;
+
+// Unsigned integer types a bit field can be read into.
+pub trait FromBits: Sized {
+    const BITS: u32;
+
+    fn from_bits(value: u64) -> Self;
+}
+
+macro_rules! impl_from_bits {
+    ($($t:ty),*) => {
+        $(
+            impl FromBits for $t {
+                const BITS: u32 = (mem::size_of::<$t>() * 8) as u32;
+
+                fn from_bits(value: u64) -> $t {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_from_bits!(u8, u16, u32, u64, usize);
+
+// Fibonacci integers terminate with two consecutive 1 bits; no u64 needs more
+// than 92 bits (F(93) is the largest Fibonacci number that fits).
+const MAX_FIBONACCI_BITS: usize = 92;
+
+// Big-endian bit cursor over an in-memory consent string. Reads never allocate.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, pos: 0 }
+    }
+
+    // Number of bits left to read
+    pub fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, Error> {
+        let byte = self.data.get(self.pos / 8).ok_or(Error::UnexpectedEof)?;
+        let bit = byte >> (7 - self.pos % 8) & 1;
+        self.pos += 1;
+        Ok(bit == 1)
+    }
+
+    // Reads an unsigned integer of up to 64 bits.
+    pub fn read(&mut self, bits: u32) -> Result<u64, Error> {
+        debug_assert!(bits <= 64);
+
+        if bits as usize > self.remaining() {
+            return Err(Error::UnexpectedEof);
+        }
+
+        if bits == 0 {
+            return Ok(0);
+        }
+
+        // load the (at most 9) bytes spanning the field into a window and
+        // shift the field out of it
+        let start = self.pos / 8;
+        let end = (self.pos + bits as usize).div_ceil(8);
+        let window = self.data[start..end]
+            .iter()
+            .fold(0u128, |window, b| window << 8 | *b as u128);
+
+        let unused = ((end - start) * 8 - self.pos % 8) as u32 - bits;
+        let value = (window >> unused) as u64 & (u64::MAX >> (64 - bits));
+        self.pos += bits as usize;
+        Ok(value)
+    }
+
+    // Reads an unsigned integer of `bits` bits into the given type,
+    // e.g. `reader.read_bits::<u16>(12)` for a CMP ID.
+    pub fn read_bits<T: FromBits>(&mut self, bits: u32) -> Result<T, Error> {
+        if bits > T::BITS {
+            return Err(Error::Other(format!(
+                "Cannot read {} bits into a {}-bit integer",
+                bits,
+                T::BITS
+            )));
+        }
+        self.read(bits).map(T::from_bits)
+    }
+
+    // Reads a Fibonacci-coded positive integer.
+    pub fn read_fibonacci(&mut self) -> Result<u64, Error> {
+        let (mut a, mut b) = (1u64, 2u64);
+        let mut value = 0u64;
+        let mut last = false;
+
+        for _ in 0..=MAX_FIBONACCI_BITS {
+            let bit = self.read_bit()?;
+            if bit && last {
+                return Ok(value);
+            }
+            if bit {
+                value = value
+                    .checked_add(a)
+                    .ok_or_else(|| Error::Other(String::from("Fibonacci integer overflow")))?;
+            }
+            last = bit;
+
+            let next = a.saturating_add(b);
+            a = b;
+            b = next;
+        }
+
+        Err(Error::Other(String::from("Fibonacci integer overflow")))
+    }
+
+    // Reads a 6-bit letter offset from `base` (b'a' for v1, b'A' for v2).
+    pub fn read_letter(&mut self, base: u8) -> Result<u8, Error> {
+        match self.read_bits::<u8>(6)? {
+            offset if offset < 26 => Ok(base + offset),
+            offset => Err(Error::Other(format!("Invalid letter offset: {}", offset))),
+        }
+    }
+}
+
+// Big-endian bit writer, padding the last byte with zeros.
+pub struct BitWriter {
+    data: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        BitWriter {
+            data: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit {
+            let last = self.data.len() - 1;
+            self.data[last] |= 1 << (7 - self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    // Writes an unsigned integer of up to 64 bits, failing if it does not fit.
+    pub fn write(&mut self, bits: u32, value: u64) -> Result<(), Error> {
+        debug_assert!(bits <= 64);
+
+        if bits < 64 && value >> bits != 0 {
+            return Err(Error::Other(format!(
+                "Value {} does not fit in {} bits",
+                value, bits
+            )));
+        }
+
+        for i in (0..bits).rev() {
+            self.write_bit(value >> i & 1 == 1);
+        }
+        Ok(())
+    }
+
+    // Writes a positive integer using Fibonacci coding.
+    pub fn write_fibonacci(&mut self, value: u64) -> Result<(), Error> {
+        if value == 0 {
+            return Err(Error::Other(String::from(
+                "Fibonacci integers must be positive",
+            )));
+        }
+
+        let mut fibs = [0u64; MAX_FIBONACCI_BITS];
+        let mut len = 0;
+        let (mut a, mut b) = (1u64, 2u64);
+        while len < MAX_FIBONACCI_BITS && a <= value {
+            fibs[len] = a;
+            len += 1;
+
+            let next = a.saturating_add(b);
+            a = b;
+            b = next;
+        }
+
+        // greedy (Zeckendorf) representation, largest term first
+        let mut bits = [false; MAX_FIBONACCI_BITS];
+        let mut left = value;
+        for i in (0..len).rev() {
+            if fibs[i] <= left {
+                bits[i] = true;
+                left -= fibs[i];
+            }
+        }
+
+        for bit in &bits[..len] {
+            self.write_bit(*bit);
+        }
+        self.write_bit(true);
+        Ok(())
+    }
+
+    // Writes a 6-bit letter offset from `base` (b'a' for v1, b'A' for v2).
+    pub fn write_letter(&mut self, base: u8, letter: u8) -> Result<(), Error> {
+        match letter.wrapping_sub(base) {
+            offset if offset < 26 => self.write(6, offset as u64),
+            _ => Err(Error::Other(format!("Invalid letter: {}", letter as char))),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Default for BitWriter {
+    fn default() -> BitWriter {
+        BitWriter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write(6, 1).unwrap();
+        writer.write_bit(true);
+        writer.write(36, 15100561554).unwrap();
+        writer.write(12, 4095).unwrap();
+        assert!(writer.write(12, 4096).is_err());
+
+        let data = writer.into_bytes();
+        assert_eq!(data.len(), 7);
+
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read(6).unwrap(), 1);
+        assert!(reader.read_bit().unwrap());
+        assert_eq!(reader.read(36).unwrap(), 15100561554);
+        assert_eq!(reader.read(12).unwrap(), 4095);
+        assert_eq!(reader.read(1).unwrap(), 0);
+        assert!(reader.read(8).is_err());
+    }
+
+    #[test]
+    fn read_bits_unaligned() {
+        let data = [0b1011_0110, 0b0101_1100, 0b1111_0000];
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits::<u8>(3).unwrap(), 0b101);
+        assert_eq!(reader.read_bits::<u16>(12).unwrap(), 0b1011_0010_1110);
+        assert_eq!(reader.read_bits::<u32>(5).unwrap(), 0b0_1111);
+        assert_eq!(reader.remaining(), 4);
+        assert!(reader.read_bits::<u8>(5).is_err());
+    }
+
+    #[test]
+    fn read_bits_too_wide() {
+        let data = [0xff; 4];
+        let mut reader = BitReader::new(&data);
+        assert!(reader.read_bits::<u8>(9).is_err());
+        assert!(reader.read_bits::<u16>(17).is_err());
+        assert_eq!(reader.remaining(), 32);
+        assert_eq!(reader.read_bits::<u8>(8).unwrap(), 0xff);
+    }
+
+    #[test]
+    fn fibonacci_round_trip() {
+        // 1 = 11, 2 = 011, 3 = 0011, 4 = 1011, 12 = 101011
+        let mut writer = BitWriter::new();
+        for value in &[1, 2, 3, 4, 12] {
+            writer.write_fibonacci(*value).unwrap();
+        }
+        assert_eq!(
+            writer.into_bytes(),
+            vec![0b11011001, 0b11011101, 0b01100000]
+        );
+
+        let mut writer = BitWriter::new();
+        for value in &[1, 2, 3, 4, 12, 1000, u64::MAX] {
+            writer.write_fibonacci(*value).unwrap();
+        }
+        assert!(writer.write_fibonacci(0).is_err());
+
+        let data = writer.into_bytes();
+        let mut reader = BitReader::new(&data);
+        for value in &[1, 2, 3, 4, 12, 1000, u64::MAX] {
+            assert_eq!(reader.read_fibonacci().unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn fibonacci_unterminated() {
+        let data = [0u8; 16];
+        let mut reader = BitReader::new(&data);
+        assert!(reader.read_fibonacci().is_err());
+
+        let data = [0b1010_1010; 16];
+        let mut reader = BitReader::new(&data);
+        assert!(reader.read_fibonacci().is_err());
+    }
+
+    #[test]
+    fn letters() {
+        let mut writer = BitWriter::new();
+        writer.write_letter(b'A', b'E').unwrap();
+        writer.write_letter(b'a', b'n').unwrap();
+        assert!(writer.write_letter(b'A', b'1').is_err());
+        assert!(writer.write_letter(b'A', b'e').is_err());
+        writer.write(6, 26).unwrap();
+
+        let data = writer.into_bytes();
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_letter(b'A').unwrap(), b'E');
+        assert_eq!(reader.read_letter(b'a').unwrap(), b'n');
+        assert!(reader.read_letter(b'A').is_err());
+    }
+}
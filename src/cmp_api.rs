@@ -0,0 +1,492 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Responses of the IAB CMP JavaScript APIs, for answering them server-side:
+// getConsentData and getVendorConsents from the v1 __cmp API, and getTCData
+// from the v2 __tcfapi API. ID-keyed maps serialize with string keys.
+
+use std::collections::BTreeMap;
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+
+use serde_json;
+
+use ids::{FeatureId, PurposeId, VendorId};
+use timestamp::Deciseconds;
+use vendor_consent::{self, VendorConsent, V1};
+use vendor_list::VendorList;
+
+// __cmp('getConsentData')
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsentData {
+    #[serde(rename = "consentData")]
+    pub consent_data: String,
+
+    #[serde(rename = "gdprApplies")]
+    pub gdpr_applies: bool,
+
+    // Whether consent is shared across sites rather than stored per site
+    #[serde(rename = "hasGlobalScope")]
+    pub has_global_scope: bool,
+}
+
+impl ConsentData {
+    // The consent string is checked to decode, and passed through unchanged.
+    pub fn new(consent_data: &str, gdpr_applies: bool) -> Result<ConsentData, Error> {
+        consent_data.parse::<VendorConsent>()?;
+
+        Ok(ConsentData {
+            consent_data: consent_data.to_string(),
+            gdpr_applies,
+            has_global_scope: false,
+        })
+    }
+}
+
+// __cmp('getVendorConsents')
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VendorConsents {
+    // See V1::metadata_string()
+    pub metadata: String,
+
+    #[serde(rename = "gdprApplies")]
+    pub gdpr_applies: bool,
+
+    #[serde(rename = "hasGlobalScope")]
+    pub has_global_scope: bool,
+
+    #[serde(rename = "purposeConsents")]
+    pub purpose_consents: BTreeMap<PurposeId, bool>,
+
+    #[serde(rename = "vendorConsents")]
+    pub vendor_consents: BTreeMap<VendorId, bool>,
+}
+
+impl VendorConsents {
+    // Covers every purpose in the vendor list, and the given vendors or every
+    // vendor in the vendor list. Vendors deleted from the vendor list by `now`
+    // are not consented.
+    pub fn new(
+        consent: &V1,
+        vendor_list: &VendorList,
+        now: Deciseconds,
+        vendor_ids: Option<&[VendorId]>,
+        gdpr_applies: bool,
+    ) -> Result<VendorConsents, Error> {
+        let vendor_ids: Vec<VendorId> = match vendor_ids {
+            Some(ids) => ids.to_vec(),
+            None => vendor_list.vendors.keys().cloned().collect(),
+        };
+
+        Ok(VendorConsents {
+            metadata: consent.metadata_string()?,
+            gdpr_applies,
+            has_global_scope: false,
+            purpose_consents: vendor_list
+                .purposes
+                .keys()
+                .map(|id| (*id, consent.has_purpose_consent(*id)))
+                .collect(),
+            vendor_consents: vendor_ids
+                .into_iter()
+                .map(|id| {
+                    (
+                        id,
+                        is_active(vendor_list, id, now) && consent.has_vendor_consent(id),
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+// Vendors deleted from the vendor list are never reported as consented.
+fn is_active(vendor_list: &VendorList, id: VendorId, now: Deciseconds) -> bool {
+    vendor_list
+        .vendors
+        .get(&id)
+        .is_some_and(|v| v.is_active_at(now))
+}
+
+// Event that caused a TCData to be returned to a listener
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStatus {
+    #[serde(rename = "tcloaded")]
+    TcLoaded,
+    #[serde(rename = "cmpuishown")]
+    CmpUiShown,
+    #[serde(rename = "useractioncomplete")]
+    UserActionComplete,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpLoadStatus {
+    #[serde(rename = "stub")]
+    Stub,
+    #[serde(rename = "loading")]
+    Loading,
+    #[serde(rename = "loaded")]
+    Loaded,
+    #[serde(rename = "error")]
+    Error,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Signals<K: Ord> {
+    pub consents: BTreeMap<K, bool>,
+
+    #[serde(rename = "legitimateInterests")]
+    pub legitimate_interests: BTreeMap<K, bool>,
+}
+
+impl<K: Ord> Default for Signals<K> {
+    fn default() -> Signals<K> {
+        Signals {
+            consents: BTreeMap::new(),
+            legitimate_interests: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OutOfBand {
+    #[serde(rename = "allowedVendors")]
+    pub allowed_vendors: BTreeMap<VendorId, bool>,
+
+    #[serde(rename = "disclosedVendors")]
+    pub disclosed_vendors: BTreeMap<VendorId, bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Publisher {
+    pub consents: BTreeMap<PurposeId, bool>,
+
+    #[serde(rename = "legitimateInterests")]
+    pub legitimate_interests: BTreeMap<PurposeId, bool>,
+
+    #[serde(rename = "customPurpose")]
+    pub custom_purpose: Signals<PurposeId>,
+
+    // Restriction type (see RestrictionType::as_u8) by purpose and vendor
+    pub restrictions: BTreeMap<PurposeId, BTreeMap<VendorId, u8>>,
+}
+
+// __tcfapi('getTCData') and event listener callbacks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TcData {
+    #[serde(rename = "tcString")]
+    pub tc_string: String,
+
+    #[serde(rename = "tcfPolicyVersion")]
+    pub tcf_policy_version: u8,
+
+    #[serde(rename = "cmpId")]
+    pub cmp_id: u16,
+
+    #[serde(rename = "cmpVersion")]
+    pub cmp_version: u16,
+
+    #[serde(rename = "gdprApplies")]
+    pub gdpr_applies: bool,
+
+    #[serde(rename = "eventStatus")]
+    pub event_status: EventStatus,
+
+    #[serde(rename = "cmpStatus")]
+    pub cmp_status: CmpLoadStatus,
+
+    // Only set in event listener callbacks
+    #[serde(
+        rename = "listenerId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub listener_id: Option<u32>,
+
+    #[serde(rename = "isServiceSpecific")]
+    pub is_service_specific: bool,
+
+    #[serde(rename = "useNonStandardStacks")]
+    pub use_non_standard_stacks: bool,
+
+    #[serde(rename = "publisherCC")]
+    pub publisher_cc: String,
+
+    #[serde(rename = "purposeOneTreatment")]
+    pub purpose_one_treatment: bool,
+
+    #[serde(rename = "outOfBand")]
+    pub out_of_band: OutOfBand,
+
+    pub purpose: Signals<PurposeId>,
+
+    pub vendor: Signals<VendorId>,
+
+    #[serde(rename = "specialFeatureOptins")]
+    pub special_feature_opt_ins: BTreeMap<FeatureId, bool>,
+
+    pub publisher: Publisher,
+}
+
+impl TcData {
+    // Covers every purpose, special feature and vendor in the vendor list, with
+    // vendors deleted from it by `now` reported as neither consented nor under
+    // legitimate interest. The TC string is passed through unchanged. Only its
+    // core segment is decoded, so the out-of-band and publisher signals are empty.
+    pub fn new(
+        tc_string: &str,
+        vendor_list: &VendorList,
+        now: Deciseconds,
+        gdpr_applies: bool,
+        event_status: EventStatus,
+    ) -> Result<TcData, Error> {
+        let consent = match tc_string.parse()? {
+            VendorConsent::V2(v2) => v2,
+            VendorConsent::V1(_) => {
+                return Err(Error::Other(
+                    "getTCData requires a TCF v2 consent string".to_string(),
+                ))
+            }
+        };
+
+        let purpose = Signals {
+            consents: vendor_list
+                .purposes
+                .keys()
+                .map(|id| (*id, consent.has_purpose_consent(*id)))
+                .collect(),
+            legitimate_interests: vendor_list
+                .purposes
+                .keys()
+                .map(|id| (*id, consent.purposes_li_transparency.contains(*id)))
+                .collect(),
+        };
+
+        let vendor = Signals {
+            consents: vendor_list
+                .vendors
+                .keys()
+                .map(|id| {
+                    (
+                        *id,
+                        consent.has_vendor_consent(*id) && is_active(vendor_list, *id, now),
+                    )
+                })
+                .collect(),
+            legitimate_interests: vendor_list
+                .vendors
+                .keys()
+                .map(|id| {
+                    let established = consent.has_vendor_legitimate_interest(*id);
+                    (*id, established && is_active(vendor_list, *id, now))
+                })
+                .collect(),
+        };
+
+        let mut restrictions: BTreeMap<PurposeId, BTreeMap<VendorId, u8>> = BTreeMap::new();
+        for restriction in &consent.publisher_restrictions {
+            let vendors = restrictions.entry(restriction.purpose_id).or_default();
+            for id in restriction.vendors.iter() {
+                vendors.insert(id, restriction.restriction_type.as_u8());
+            }
+        }
+
+        Ok(TcData {
+            tc_string: tc_string.to_string(),
+            tcf_policy_version: consent.tcf_policy_version,
+            cmp_id: consent.cmp_id,
+            cmp_version: consent.cmp_version,
+            gdpr_applies,
+            event_status,
+            cmp_status: CmpLoadStatus::Loaded,
+            listener_id: None,
+            is_service_specific: consent.is_service_specific,
+            use_non_standard_stacks: consent.use_non_standard_stacks,
+            publisher_cc: consent.publisher_cc.clone(),
+            purpose_one_treatment: consent.purpose_one_treatment,
+            out_of_band: OutOfBand::default(),
+            purpose,
+            vendor,
+            special_feature_opt_ins: vendor_list
+                .special_features
+                .keys()
+                .map(|id| (*id, consent.has_special_feature_opt_in(*id)))
+                .collect(),
+            publisher: Publisher {
+                restrictions,
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    VendorConsentError(vendor_consent::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::JsonError(ref err) => Some(err),
+            Error::VendorConsentError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::JsonError(ref err) => Display::fmt(err, f),
+            Error::VendorConsentError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+impl From<vendor_consent::Error> for Error {
+    fn from(e: vendor_consent::Error) -> Error {
+        Error::VendorConsentError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_fixtures::{now, v1_vendor_list, v2_vendor_list, V1_CONSENT, V2_CONSENT};
+
+    fn parse_v1() -> V1 {
+        match V1_CONSENT.parse().unwrap() {
+            VendorConsent::V1(v1) => v1,
+            other => panic!("expected a v1 consent string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn get_consent_data() {
+        let data = ConsentData::new(V1_CONSENT, true).unwrap();
+        assert_eq!(
+            serde_json::to_string(&data).unwrap(),
+            r#"{"consentData":"BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA","gdprApplies":true,"hasGlobalScope":false}"#
+        );
+        assert!(ConsentData::new("not a consent string", true).is_err());
+    }
+
+    #[test]
+    fn get_vendor_consents() {
+        let consent = parse_v1();
+        let vendor_list = v1_vendor_list();
+
+        let all = VendorConsents::new(&consent, &vendor_list, now(), None, true).unwrap();
+        let json = serde_json::to_string(&all).unwrap();
+        assert_eq!(
+            json,
+            r#"{"metadata":"BOEFEAyOEFEAyAHABDAI","gdprApplies":true,"hasGlobalScope":false,"purposeConsents":{"1":true,"2":true,"4":false},"vendorConsents":{"1":true,"2":true,"9":false}}"#
+        );
+        assert_eq!(serde_json::from_str::<VendorConsents>(&json).unwrap(), all);
+
+        // vendors missing from the vendor list are not consented
+        let some = VendorConsents::new(
+            &consent,
+            &vendor_list,
+            now(),
+            Some(&[VendorId(2), VendorId(3)]),
+            false,
+        )
+        .unwrap();
+        assert!(!some.gdpr_applies);
+        assert_eq!(
+            serde_json::to_string(&some.vendor_consents).unwrap(),
+            r#"{"2":true,"3":false}"#
+        );
+    }
+
+    #[test]
+    fn get_tc_data() {
+        let vendor_list = v2_vendor_list();
+
+        let tc_data =
+            TcData::new(V2_CONSENT, &vendor_list, now(), true, EventStatus::TcLoaded).unwrap();
+        let json = tc_data.to_string().unwrap();
+        // vendor 8 has consent in the string but is deleted from the list
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"tcString":"COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA","#,
+                r#""tcfPolicyVersion":2,"cmpId":27,"cmpVersion":0,"gdprApplies":true,"#,
+                r#""eventStatus":"tcloaded","cmpStatus":"loaded","isServiceSpecific":false,"#,
+                r#""useNonStandardStacks":false,"publisherCC":"AA","purposeOneTreatment":false,"#,
+                r#""outOfBand":{"allowedVendors":{},"disclosedVendors":{}},"#,
+                r#""purpose":{"consents":{"1":true,"2":true,"3":true},"#,
+                r#""legitimateInterests":{"1":false,"2":false,"3":false}},"#,
+                r#""vendor":{"consents":{"1":false,"2":true,"3":false,"6":true,"8":false},"#,
+                r#""legitimateInterests":{"1":false,"2":true,"3":false,"6":true,"8":false}},"#,
+                r#""specialFeatureOptins":{"1":false},"#,
+                r#""publisher":{"consents":{},"legitimateInterests":{},"#,
+                r#""customPurpose":{"consents":{},"legitimateInterests":{}},"restrictions":{}}}"#
+            )
+        );
+        assert_eq!(serde_json::from_str::<TcData>(&json).unwrap(), tc_data);
+
+        // before its deletion vendor 8 is still consented
+        let before = TcData::new(
+            V2_CONSENT,
+            &vendor_list,
+            "2020-05-01T00:00:00Z".parse().unwrap(),
+            true,
+            EventStatus::TcLoaded,
+        )
+        .unwrap();
+        assert!(before.vendor.consents[&VendorId(8)]);
+
+        let listener: TcData = serde_json::from_str(
+            &json.replace(r#""tcloaded""#, r#""useractioncomplete","listenerId":3"#),
+        )
+        .unwrap();
+        assert_eq!(listener.event_status, EventStatus::UserActionComplete);
+        assert_eq!(listener.listener_id, Some(3));
+    }
+
+    #[test]
+    fn tc_string_passed_through() {
+        let tc_string = format!("{}.YAAAAAAAAAAA", V2_CONSENT);
+        let tc_data = TcData::new(
+            &tc_string,
+            &v2_vendor_list(),
+            now(),
+            true,
+            EventStatus::TcLoaded,
+        )
+        .unwrap();
+        assert_eq!(tc_data.tc_string, tc_string);
+
+        assert!(TcData::new(
+            V1_CONSENT,
+            &v2_vendor_list(),
+            now(),
+            true,
+            EventStatus::TcLoaded
+        )
+        .is_err());
+    }
+}
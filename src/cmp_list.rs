@@ -0,0 +1,188 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde_json;
+
+use ids::CmpId;
+use timestamp::Deciseconds;
+use vendor_list::{deserialize_list_as_id_map, serialize_id_map_as_list, HasId};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Cmp {
+    pub id: CmpId,
+    pub name: String,
+    #[serde(rename = "isCommercial", default)]
+    pub is_commercial: bool,
+    // When the CMP was removed from the list, if it has been
+    #[serde(
+        rename = "deletedDate",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_date: Option<DateTime<Utc>>,
+}
+
+impl HasId<CmpId> for Cmp {
+    fn id(&self) -> CmpId {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpStatus {
+    Registered,
+    Unknown,
+    Deleted(DateTime<Utc>),
+}
+
+// The IAB list of registered consent management platforms (cmp-list.json)
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CmpList {
+    #[serde(rename = "lastUpdated")]
+    pub last_updated: DateTime<Utc>,
+
+    #[serde(
+        serialize_with = "serialize_id_map_as_list",
+        deserialize_with = "deserialize_list_as_id_map"
+    )]
+    pub cmps: HashMap<CmpId, Cmp>,
+}
+
+impl CmpList {
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+
+    // Whether a consent string from the given CMP created at the given time
+    // came from a CMP registered at the time.
+    pub fn status<I: Into<CmpId>>(&self, id: I, created: Deciseconds) -> CmpStatus {
+        match self.cmps.get(&id.into()) {
+            None => CmpStatus::Unknown,
+            Some(&Cmp {
+                deleted_date: Some(deleted),
+                ..
+            }) if created.to_datetime() >= deleted => CmpStatus::Deleted(deleted),
+            Some(_) => CmpStatus::Registered,
+        }
+    }
+}
+
+impl FromStr for CmpList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(From::from)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::JsonError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::JsonError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use vendor_consent::{ValidationOptions, VendorConsent};
+
+    const CMP_LIST: &str = r#"{
+        "lastUpdated": "2020-08-05T16:00:09Z",
+        "cmps": {
+            "2": {
+                "id": 2,
+                "name": "Example CMP",
+                "isCommercial": true,
+                "environments": ["Web"]
+            },
+            "7": {
+                "id": 7,
+                "name": "Deleted CMP",
+                "isCommercial": false,
+                "deletedDate": "2018-01-01T00:00:00Z"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn deserialize_good() {
+        let cmp_list: CmpList = CMP_LIST.parse().unwrap();
+        assert_eq!(cmp_list.cmps.len(), 2);
+        assert!(cmp_list.cmps[&2].is_commercial);
+
+        let created = "2017-11-07T19:15:55.4Z".parse().unwrap();
+        assert_eq!(cmp_list.status(2, created), CmpStatus::Registered);
+        assert_eq!(cmp_list.status(3, created), CmpStatus::Unknown);
+        assert_eq!(cmp_list.status(7, created), CmpStatus::Registered);
+        assert_eq!(
+            cmp_list.status(7, "2018-05-11T12:00:00Z".parse().unwrap()),
+            CmpStatus::Deleted("2018-01-01T00:00:00Z".parse().unwrap())
+        );
+
+        // v1 lists are arrays
+        let v1: CmpList = r#"{
+            "lastUpdated": "2018-05-28T16:00:15Z",
+            "cmps": [{ "id": 2, "name": "Example CMP", "isCommercial": true }]
+        }"#
+        .parse()
+        .unwrap();
+        assert_eq!(v1.cmps[&2].name, "Example CMP");
+        assert_eq!(v1.to_string().unwrap().parse::<CmpList>().unwrap(), v1);
+    }
+
+    #[test]
+    fn validate_cmp_id() {
+        // created by CMP ID 7 on 2017-11-07
+        let s = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+        let mut options = ValidationOptions {
+            cmp_list: Some(Arc::new(CMP_LIST.parse().unwrap())),
+            ..Default::default()
+        };
+        assert!(VendorConsent::parse_with(s, &options).is_ok());
+
+        Arc::get_mut(options.cmp_list.as_mut().unwrap())
+            .unwrap()
+            .cmps
+            .remove(&7);
+        assert!(VendorConsent::parse_with(s, &options).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use ids::{PurposeId, VendorId};
+use timestamp::Deciseconds;
+use vendor_consent::VendorConsent;
+
+// Purposes are stored in a 24 bit field
+const MAX_PURPOSE_ID: u8 = 24;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Change<T> {
+    pub from: T,
+    pub to: T,
+}
+
+fn change<T: PartialEq + Clone>(from: &T, to: &T) -> Option<Change<T>> {
+    if from == to {
+        None
+    } else {
+        Some(Change {
+            from: from.clone(),
+            to: to.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ConsentDiff {
+    #[serde(rename = "purposesGranted")]
+    pub purposes_granted: Vec<PurposeId>,
+
+    #[serde(rename = "purposesRevoked")]
+    pub purposes_revoked: Vec<PurposeId>,
+
+    #[serde(rename = "vendorsAdded")]
+    pub vendors_added: Vec<VendorId>,
+
+    #[serde(rename = "vendorsRemoved")]
+    pub vendors_removed: Vec<VendorId>,
+
+    #[serde(rename = "cmpId", skip_serializing_if = "Option::is_none")]
+    pub cmp_id: Option<Change<u16>>,
+
+    #[serde(rename = "cmpVersion", skip_serializing_if = "Option::is_none")]
+    pub cmp_version: Option<Change<u16>>,
+
+    #[serde(rename = "consentScreen", skip_serializing_if = "Option::is_none")]
+    pub consent_screen: Option<Change<u8>>,
+
+    #[serde(rename = "consentLanguage", skip_serializing_if = "Option::is_none")]
+    pub consent_language: Option<Change<String>>,
+
+    #[serde(rename = "vendorListVersion", skip_serializing_if = "Option::is_none")]
+    pub vendor_list_version: Option<Change<u16>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<Change<Deciseconds>>,
+
+    #[serde(rename = "lastUpdated", skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<Change<Deciseconds>>,
+}
+
+impl ConsentDiff {
+    // Whether the two consent strings grant exactly the same consent. Changes
+    // to metadata (CMP, language, timestamps, ...) are not taken into account.
+    pub fn same_consent(&self) -> bool {
+        self.purposes_granted.is_empty()
+            && self.purposes_revoked.is_empty()
+            && self.vendors_added.is_empty()
+            && self.vendors_removed.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ConsentDiff::default()
+    }
+}
+
+impl VendorConsent {
+    // Describes the changes needed to go from this consent to `other`.
+    //
+    // Vendors are compared by ID, with vendors above a string's max_vendor_id
+    // treated as not consented, so a string that merely covers a wider vendor
+    // range only reports the newly consented vendors as added. Legitimate
+    // interest signals of TCF v2 strings are not compared.
+    pub fn diff(&self, other: &VendorConsent) -> ConsentDiff {
+        let (from, to) = (self, other);
+        let mut diff = ConsentDiff::default();
+
+        for id in (1..=MAX_PURPOSE_ID).map(PurposeId) {
+            match (from.has_purpose_consent(id), to.has_purpose_consent(id)) {
+                (false, true) => diff.purposes_granted.push(id),
+                (true, false) => diff.purposes_revoked.push(id),
+                _ => {}
+            }
+        }
+
+        // max_vendor_id can be set above the highest vendor ID by hand; no
+        // vendor can have a higher ID than u16::MAX
+        let max_vendor_id = cmp::max(from.max_vendor_id(), to.max_vendor_id());
+        let max_vendor_id = u16::try_from(max_vendor_id).unwrap_or(u16::MAX);
+        for id in (1..=max_vendor_id).map(VendorId) {
+            match (from.has_vendor_consent(id), to.has_vendor_consent(id)) {
+                (false, true) => diff.vendors_added.push(id),
+                (true, false) => diff.vendors_removed.push(id),
+                _ => {}
+            }
+        }
+
+        diff.cmp_id = change(&from.cmp_id(), &to.cmp_id());
+        diff.cmp_version = change(&from.cmp_version(), &to.cmp_version());
+        diff.consent_screen = change(&from.consent_screen(), &to.consent_screen());
+        diff.consent_language = change(
+            &from.consent_language().to_string(),
+            &to.consent_language().to_string(),
+        );
+        diff.vendor_list_version = change(&from.vendor_list_version(), &to.vendor_list_version());
+        diff.created = change(&from.created(), &to.created());
+        diff.last_updated = change(&from.last_updated(), &to.last_updated());
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bit_set::BitSet;
+    use bit_vec::BitVec;
+
+    use ids::{PurposeSet, VendorSet};
+    use vendor_consent::V1;
+
+    fn consent(max_vendor_id: usize, refused: &[usize]) -> V1 {
+        let mut vendor_consent = BitVec::from_elem(max_vendor_id, true);
+        for id in refused {
+            vendor_consent.set(id - 1, false);
+        }
+
+        V1 {
+            created: "2017-11-07T19:15:55.4Z".parse().unwrap(),
+            last_updated: "2017-11-07T19:15:55.4Z".parse().unwrap(),
+            cmp_id: 7,
+            cmp_version: 1,
+            consent_screen: 3,
+            consent_language: "en".to_string(),
+            vendor_list_version: 8,
+            purposes_allowed: PurposeSet::from_bit_set(BitSet::from_bytes(&[
+                0b11100000, 0b00000000, 0b00000000,
+            ])),
+            max_vendor_id,
+            vendor_consent: VendorSet::from_bit_set(BitSet::from_bit_vec(vendor_consent)),
+        }
+    }
+
+    #[test]
+    fn diff_good() {
+        let from = consent(2011, &[9]);
+        let mut to = consent(2011, &[10]);
+        to.purposes_allowed =
+            PurposeSet::from_bit_set(BitSet::from_bytes(&[0b01110000, 0b00000000, 0b00000000]));
+        to.vendor_list_version = 9;
+        to.last_updated = "2018-05-11T12:00:00.0Z".parse().unwrap();
+
+        let diff = VendorConsent::V1(from).diff(&VendorConsent::V1(to));
+        assert_eq!(diff.purposes_granted, vec![PurposeId(4)]);
+        assert_eq!(diff.purposes_revoked, vec![PurposeId(1)]);
+        assert_eq!(diff.vendors_added, vec![VendorId(9)]);
+        assert_eq!(diff.vendors_removed, vec![VendorId(10)]);
+        assert_eq!(diff.vendor_list_version, Some(Change { from: 8, to: 9 }));
+        assert!(diff.created.is_none());
+        assert!(diff.last_updated.is_some());
+        assert!(!diff.same_consent());
+
+        assert_eq!(
+            json!(diff),
+            json!({
+                "purposesGranted": [4],
+                "purposesRevoked": [1],
+                "vendorsAdded": [9],
+                "vendorsRemoved": [10],
+                "vendorListVersion": { "from": 8, "to": 9 },
+                "lastUpdated": { "from": "2017-11-07T19:15:55.400Z", "to": "2018-05-11T12:00:00Z" }
+            })
+        );
+    }
+
+    #[test]
+    fn diff_grown_vendor_range() {
+        let from = consent(10, &[9]);
+        let to = consent(12, &[9]);
+
+        let diff = VendorConsent::V1(from).diff(&VendorConsent::V1(to));
+        assert_eq!(diff.vendors_added, vec![VendorId(11), VendorId(12)]);
+        assert!(diff.vendors_removed.is_empty());
+        assert!(diff.purposes_revoked.is_empty());
+
+        let same = VendorConsent::V1(consent(10, &[9])).diff(&VendorConsent::V1(consent(10, &[9])));
+        assert!(same.is_empty());
+    }
+
+    #[test]
+    fn diff_max_vendor_id_past_u16() {
+        let from = consent(10, &[]);
+        let to = consent(70_000, &[]);
+
+        let diff = VendorConsent::V1(from).diff(&VendorConsent::V1(to));
+        assert_eq!(diff.vendors_added.len(), 65_525);
+        assert_eq!(diff.vendors_added.last(), Some(&VendorId(65_535)));
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+
+use ids::VendorId;
+use timestamp::Deciseconds;
+use vendor_consent::VendorConsent;
+use vendor_list::VendorList;
+
+// When a CMP should show its UI again for a previously stored consent string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreshnessPolicy {
+    // Consent last updated more than this many calendar months ago has expired
+    pub max_age_months: u32,
+
+    // Consent collected against a vendor list more than this many versions
+    // older than the current one is stale; None never considers it stale
+    pub max_versions_behind: Option<u16>,
+
+    // Whether vendors added to the vendor list since consent was collected
+    // require showing the UI again
+    pub reprompt_for_new_vendors: bool,
+}
+
+impl Default for FreshnessPolicy {
+    fn default() -> FreshnessPolicy {
+        FreshnessPolicy {
+            max_age_months: 13,
+            max_versions_behind: Some(0),
+            reprompt_for_new_vendors: true,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Freshness {
+    // None if the expiry date is too far in the future to represent
+    pub expires: Option<DateTime<Utc>>,
+
+    pub expired: bool,
+
+    // Vendor list versions released since consent was collected, None when
+    // evaluated without a vendor list
+    #[serde(rename = "versionsBehind")]
+    pub versions_behind: Option<u16>,
+
+    pub stale: bool,
+
+    // Vendors in the current vendor list past the consent string's max
+    // vendor ID, which the user cannot have been presented with. Vendors
+    // deleted by the time of evaluation are left out.
+    #[serde(rename = "newVendors")]
+    pub new_vendors: Vec<VendorId>,
+}
+
+impl Freshness {
+    pub fn needs_reprompt(&self) -> bool {
+        self.expired || self.stale || !self.new_vendors.is_empty()
+    }
+}
+
+impl FreshnessPolicy {
+    pub fn evaluate(
+        &self,
+        consent: &VendorConsent,
+        now: Deciseconds,
+        vendor_list: Option<&VendorList>,
+    ) -> Freshness {
+        let expires = add_months(consent.last_updated().to_datetime(), self.max_age_months);
+        let expired = expires.is_some_and(|t| now.to_datetime() >= t);
+
+        let versions_behind =
+            vendor_list.map(|l| l.version.saturating_sub(consent.vendor_list_version()));
+        let stale = match (versions_behind, self.max_versions_behind) {
+            (Some(behind), Some(max)) => behind > max,
+            _ => false,
+        };
+
+        let mut new_vendors: Vec<VendorId> = match vendor_list {
+            Some(l) if self.reprompt_for_new_vendors => l
+                .vendors
+                .values()
+                .filter(|v| v.id.0 as usize > consent.max_vendor_id())
+                .filter(|v| v.is_active_at(now))
+                .map(|v| v.id)
+                .collect(),
+            _ => Vec::new(),
+        };
+        new_vendors.sort();
+
+        Freshness {
+            expires,
+            expired,
+            versions_behind,
+            stale,
+            new_vendors,
+        }
+    }
+}
+
+// Same day of the month, clamped to the last day of shorter months.
+fn add_months(t: DateTime<Utc>, months: u32) -> Option<DateTime<Utc>> {
+    let month0 = i64::from(t.month0()) + i64::from(months);
+    // at most ~358 million years ahead, so this fits in an i32
+    let year = (i64::from(t.year()) + month0 / 12) as i32;
+    let month = (month0 % 12) as u32 + 1;
+    let date = (1..=t.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))?;
+    Some(Utc.from_utc_datetime(&date.and_time(t.time())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json;
+
+    use test_fixtures::{v1_vendor_list, V1_CONSENT};
+    use vendor_list::Vendor;
+
+    // Two versions past the consent string's, adding vendor 2012
+    fn vendor_list() -> VendorList {
+        let mut vendor_list = v1_vendor_list();
+        vendor_list.version = 10;
+        let new_vendor: Vendor = serde_json::from_str(
+            r#"{ "id": 2012, "name": "New Vendor", "policyUrl": "https://example.org/privacy", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] }"#,
+        )
+        .unwrap();
+        vendor_list.vendors.insert(new_vendor.id, new_vendor);
+        vendor_list
+    }
+
+    #[test]
+    fn evaluate_good() {
+        // last updated 2017-11-07, vendor list version 8, max vendor ID 2011
+        let consent = V1_CONSENT.parse().unwrap();
+        let policy = FreshnessPolicy::default();
+
+        let f = policy.evaluate(&consent, "2018-12-07T19:15:55.3Z".parse().unwrap(), None);
+        assert_eq!(f.expires, Some("2018-12-07T19:15:55.4Z".parse().unwrap()));
+        assert!(!f.needs_reprompt());
+
+        let f = policy.evaluate(&consent, "2018-12-07T19:15:55.4Z".parse().unwrap(), None);
+        assert!(f.expired);
+
+        let f = policy.evaluate(
+            &consent,
+            "2018-01-01T00:00:00Z".parse().unwrap(),
+            Some(&vendor_list()),
+        );
+        assert!(!f.expired);
+        assert_eq!(f.versions_behind, Some(2));
+        assert!(f.stale);
+        assert_eq!(f.new_vendors, vec![VendorId(2012)]);
+
+        let lenient = FreshnessPolicy {
+            max_versions_behind: Some(2),
+            reprompt_for_new_vendors: false,
+            ..Default::default()
+        };
+        let f = lenient.evaluate(
+            &consent,
+            "2018-01-01T00:00:00Z".parse().unwrap(),
+            Some(&vendor_list()),
+        );
+        assert!(!f.needs_reprompt());
+    }
+
+    #[test]
+    fn add_months_clamps_day() {
+        let t = "2019-01-31T12:00:00Z".parse().unwrap();
+        assert_eq!(
+            add_months(t, 13),
+            Some("2020-02-29T12:00:00Z".parse().unwrap())
+        );
+        assert_eq!(add_months(t, u32::MAX), None);
+    }
+}
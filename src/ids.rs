@@ -0,0 +1,222 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Display};
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+
+use bit_set::BitSet;
+
+// IDs as they appear in vendor lists and consent strings, starting at 1.
+// 0 is never a valid ID.
+pub trait Id: Copy {
+    // 0-based bit index of the ID in a consent string bitfield
+    fn index(self) -> Option<usize>;
+
+    // None if the ID does not fit the ID type
+    fn from_index(index: usize) -> Option<Self>;
+}
+
+macro_rules! id {
+    ($name:ident, $t:ty) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+        pub struct $name(pub $t);
+
+        impl Id for $name {
+            fn index(self) -> Option<usize> {
+                (self.0 as usize).checked_sub(1)
+            }
+
+            fn from_index(index: usize) -> Option<$name> {
+                index
+                    .checked_add(1)
+                    .and_then(|id| <$t>::try_from(id).ok())
+                    .map($name)
+            }
+        }
+
+        impl From<$t> for $name {
+            fn from(id: $t) -> $name {
+                $name(id)
+            }
+        }
+
+        // Allows maps keyed by ID to be looked up with the raw value
+        impl Borrow<$t> for $name {
+            fn borrow(&self) -> &$t {
+                &self.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+id!(VendorId, u16);
+id!(PurposeId, u8);
+id!(FeatureId, u8);
+id!(StackId, u8);
+id!(CmpId, u16);
+
+// Set of 1-based IDs backed by a 0-based bitset, as stored in consent strings.
+// Bits past the highest ID the ID type can hold are ignored.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IdSet<T> {
+    bits: BitSet,
+    id: PhantomData<T>,
+}
+
+pub type VendorSet = IdSet<VendorId>;
+pub type PurposeSet = IdSet<PurposeId>;
+pub type FeatureSet = IdSet<FeatureId>;
+
+impl<T: Id> IdSet<T> {
+    pub fn new() -> IdSet<T> {
+        IdSet::from_bit_set(BitSet::new())
+    }
+
+    // Wraps a bitset where bit 0 holds ID 1.
+    pub fn from_bit_set(bits: BitSet) -> IdSet<T> {
+        IdSet {
+            bits,
+            id: PhantomData,
+        }
+    }
+
+    pub fn as_bit_set(&self) -> &BitSet {
+        &self.bits
+    }
+
+    pub fn as_bit_set_mut(&mut self) -> &mut BitSet {
+        &mut self.bits
+    }
+
+    pub fn into_bit_set(self) -> BitSet {
+        self.bits
+    }
+
+    pub fn contains(&self, id: T) -> bool {
+        id.index().is_some_and(|i| self.bits.contains(i))
+    }
+
+    // Returns whether the ID was newly inserted, or None if the ID is 0.
+    pub fn insert(&mut self, id: T) -> Option<bool> {
+        id.index().map(|i| self.bits.insert(i))
+    }
+
+    // Returns whether the ID was present.
+    pub fn remove(&mut self, id: T) -> bool {
+        id.index().is_some_and(|i| self.bits.remove(i))
+    }
+
+    // IDs in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.bits.iter().map_while(T::from_index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    // Highest ID in the set, found from the last non-empty block of the
+    // bitset rather than by iterating it.
+    pub fn max(&self) -> Option<T> {
+        let blocks = self.bits.get_ref().storage();
+        let (i, block) = blocks.iter().enumerate().rev().find(|(_, b)| **b != 0)?;
+        let index = i * 32 + (31 - block.leading_zeros() as usize);
+        T::from_index(index).or_else(|| self.iter().last())
+    }
+}
+
+impl<T: Id> Default for IdSet<T> {
+    fn default() -> IdSet<T> {
+        IdSet::new()
+    }
+}
+
+impl<T: Id + Debug> Debug for IdSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Id> FromIterator<T> for IdSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> IdSet<T> {
+        let mut set = IdSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+// IDs of 0 are skipped: they can never be in a set.
+impl<T: Id> Extend<T> for IdSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    #[test]
+    fn id_set_is_one_based() {
+        let mut vendors: VendorSet = [VendorId(1), VendorId(10)].iter().cloned().collect();
+        assert!(vendors.contains(VendorId(1)));
+        assert!(vendors.as_bit_set().contains(0));
+        assert!(vendors.as_bit_set().contains(9));
+        assert!(!vendors.contains(VendorId(0)));
+
+        assert_eq!(vendors.insert(VendorId(3)), Some(true));
+        assert_eq!(vendors.insert(VendorId(3)), Some(false));
+        assert_eq!(vendors.insert(VendorId(0)), None);
+        assert!(vendors.remove(VendorId(10)));
+        assert!(!vendors.remove(VendorId(0)));
+
+        assert_eq!(
+            vendors.iter().collect::<Vec<_>>(),
+            vec![VendorId(1), VendorId(3)]
+        );
+        assert_eq!(vendors.max(), Some(VendorId(3)));
+        assert_eq!(vendors.len(), 2);
+        assert_eq!(format!("{:?}", vendors), "{VendorId(1), VendorId(3)}");
+    }
+
+    #[test]
+    fn id_bounds() {
+        assert_eq!(VendorId::from_index(65534), Some(VendorId(65535)));
+        assert_eq!(VendorId::from_index(65535), None);
+        assert_eq!(PurposeId::from_index(255), None);
+
+        let mut vendors: VendorSet = [VendorId(65535), VendorId(40)].iter().cloned().collect();
+        assert_eq!(vendors.max(), Some(VendorId(65535)));
+        vendors.remove(VendorId(65535));
+        assert_eq!(vendors.max(), Some(VendorId(40)));
+        assert_eq!(VendorSet::new().max(), None);
+
+        // a bit past u8::MAX cannot be a purpose
+        let purposes = PurposeSet::from_bit_set([1, 300].iter().cloned().collect());
+        assert_eq!(purposes.iter().collect::<Vec<_>>(), vec![PurposeId(2)]);
+        assert_eq!(purposes.max(), Some(PurposeId(2)));
+    }
+}
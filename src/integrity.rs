@@ -0,0 +1,462 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Integrity checks for mirrored vendor lists: SHA-256 digests of the files as
+// stored, listed in a sha256sum-style manifest, and detached ed25519
+// signatures over the canonical JSON form of the list.
+
+use std::collections::BTreeMap;
+use std::convert::{From, TryInto};
+use std::error;
+use std::fmt::{self, Display, Write};
+use std::str::{self, FromStr};
+
+use base64;
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use ed25519_dalek::{SignatureError, Signer, Verifier};
+use sha2::{Digest, Sha256};
+
+use vendor_list::{self, VendorList};
+
+pub type Sha256Digest = [u8; 32];
+
+pub fn sha256(data: &[u8]) -> Sha256Digest {
+    Sha256::digest(data).into()
+}
+
+// Expected digests by file name, in the format written by `sha256sum`:
+//
+//   <hex digest>  vendorlist-v48.json
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub digests: BTreeMap<String, Sha256Digest>,
+}
+
+impl Manifest {
+    pub fn insert(&mut self, name: &str, data: &[u8]) {
+        self.digests.insert(name.to_string(), sha256(data));
+    }
+
+    // Fails if the file is missing from the manifest or its digest differs.
+    pub fn verify(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        match self.digests.get(name) {
+            None => Err(Error::Other(format!("{} is not in the manifest", name))),
+            Some(expected) if *expected != sha256(data) => Err(Error::Other(format!(
+                "SHA-256 digest of {} does not match the manifest",
+                name
+            ))),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut manifest = Manifest::default();
+        for (i, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let invalid = || Error::Other(format!("Invalid manifest line {}: {}", i + 1, line));
+            let (digest, name) = line.split_at(line.find(' ').ok_or_else(invalid)?);
+            // binary mode entries mark the name with '*'
+            let name = name[1..].trim_start_matches([' ', '*']);
+            let digest = decode_hex(digest).ok_or_else(invalid)?;
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            manifest.digests.insert(name.to_string(), digest);
+        }
+        Ok(manifest)
+    }
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, digest) in &self.digests {
+            writeln!(f, "{}  {}", encode_hex(digest), name)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_hex(digest: &Sha256Digest) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in digest {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+fn decode_hex(hex: &str) -> Option<Sha256Digest> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(digest)
+}
+
+// The form signatures cover: the vendor list serialized as the IAB publishes
+// it, including the fields this crate does not model, so a signature survives
+// reformatting of the JSON but not edits to any field.
+pub fn canonical_json(vendor_list: &VendorList) -> Result<String, Error> {
+    vendor_list.to_string().map_err(From::from)
+}
+
+pub fn sign(vendor_list: &VendorList, key: &SigningKey) -> Result<Signature, Error> {
+    Ok(key.sign(canonical_json(vendor_list)?.as_bytes()))
+}
+
+pub fn verify_signature(
+    vendor_list: &VendorList,
+    signature: &Signature,
+    key: &VerifyingKey,
+) -> Result<(), Error> {
+    key.verify(canonical_json(vendor_list)?.as_bytes(), signature)
+        .map_err(From::from)
+}
+
+// Signatures and keys are stored as standard base64, signing keys as their
+// 32 byte secret.
+pub fn encode_signature(signature: &Signature) -> String {
+    base64::encode(signature.to_bytes())
+}
+
+pub fn decode_signature(s: &str) -> Result<Signature, Error> {
+    Ok(Signature::from_bytes(&decode_fixed(s, "signature")?))
+}
+
+pub fn encode_verifying_key(key: &VerifyingKey) -> String {
+    base64::encode(key.as_bytes())
+}
+
+pub fn decode_verifying_key(s: &str) -> Result<VerifyingKey, Error> {
+    VerifyingKey::from_bytes(&decode_fixed(s, "public key")?).map_err(From::from)
+}
+
+pub fn decode_signing_key(s: &str) -> Result<SigningKey, Error> {
+    Ok(SigningKey::from_bytes(&decode_fixed(s, "signing key")?))
+}
+
+fn decode_fixed<const N: usize>(s: &str, what: &str) -> Result<[u8; N], Error> {
+    let bytes =
+        base64::decode(s.trim()).map_err(|e| Error::Other(format!("Invalid {}: {}", what, e)))?;
+    bytes.as_slice().try_into().map_err(|_| {
+        Error::Other(format!(
+            "Invalid {}: expected {} bytes, got {}",
+            what,
+            N,
+            bytes.len()
+        ))
+    })
+}
+
+// Checks applied to vendor lists before they are used. With no manifest and
+// no key, lists are only parsed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityOptions {
+    // Reject files missing from the manifest or whose digest differs
+    pub manifest: Option<Manifest>,
+
+    // Reject lists without a valid signature by this key
+    pub public_key: Option<VerifyingKey>,
+}
+
+impl IntegrityOptions {
+    // Checks a file as stored, before decompressing or parsing it.
+    pub fn verify_file(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        match self.manifest {
+            Some(ref manifest) => manifest.verify(name, data),
+            None => Ok(()),
+        }
+    }
+
+    // Checks a parsed list against its detached signature, if any.
+    pub fn verify_signature(
+        &self,
+        name: &str,
+        vendor_list: &VendorList,
+        signature: Option<&str>,
+    ) -> Result<(), Error> {
+        let key = match self.public_key {
+            Some(ref key) => key,
+            None => return Ok(()),
+        };
+        let signature = signature
+            .ok_or_else(|| Error::Other(format!("{} is not signed", name)))
+            .and_then(decode_signature)?;
+        verify_signature(vendor_list, &signature, key)
+    }
+}
+
+// Parses a vendor list file after checking it against the manifest, then
+// checks its detached signature (base64, as written by `gvl sign`).
+pub fn load(
+    name: &str,
+    data: &[u8],
+    signature: Option<&str>,
+    options: &IntegrityOptions,
+) -> Result<VendorList, Error> {
+    options.verify_file(name, data)?;
+
+    let json =
+        str::from_utf8(data).map_err(|e| Error::Other(format!("{} is not UTF-8: {}", name, e)))?;
+    let vendor_list: VendorList = json.parse()?;
+
+    options.verify_signature(name, &vendor_list, signature)?;
+    Ok(vendor_list)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    VendorListError(vendor_list::Error),
+    SignatureError(SignatureError),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::VendorListError(ref err) => Some(err),
+            Error::SignatureError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::VendorListError(ref err) => Display::fmt(err, f),
+            Error::SignatureError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<vendor_list::Error> for Error {
+    fn from(e: vendor_list::Error) -> Error {
+        Error::VendorListError(e)
+    }
+}
+
+impl From<SignatureError> for Error {
+    fn from(e: SignatureError) -> Error {
+        Error::SignatureError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VENDOR_LIST: &str = r#"{
+        "vendorListVersion": 8,
+        "lastUpdated": "2018-05-28T16:00:15Z",
+        "purposes": [{ "id": 1, "name": "Storage and access of information", "description": "..." }],
+        "features": [],
+        "vendors": [
+            { "id": 1, "name": "Example Vendor", "policyUrl": "https://example.com/privacy", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] }
+        ]
+    }"#;
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&sha256(&[seed]))
+    }
+
+    #[test]
+    fn manifest() {
+        let mut manifest = Manifest::default();
+        manifest.insert("vendorlist-v8.json", VENDOR_LIST.as_bytes());
+        assert!(manifest
+            .verify("vendorlist-v8.json", VENDOR_LIST.as_bytes())
+            .is_ok());
+        assert!(manifest.verify("vendorlist-v8.json", b"{}").is_err());
+        assert!(manifest
+            .verify("vendorlist-v9.json", VENDOR_LIST.as_bytes())
+            .is_err());
+
+        assert_eq!(manifest.to_string().parse::<Manifest>().unwrap(), manifest);
+        let sha256sum: Manifest = "\
+            e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 *empty.json\n"
+            .parse()
+            .unwrap();
+        assert!(sha256sum.verify("empty.json", b"").is_ok());
+        assert!("e3b0c442  empty.json".parse::<Manifest>().is_err());
+    }
+
+    #[test]
+    fn signatures() {
+        let key = signing_key(1);
+        let vendor_list: VendorList = VENDOR_LIST.parse().unwrap();
+        let signature = sign(&vendor_list, &key).unwrap();
+        assert!(verify_signature(&vendor_list, &signature, &key.verifying_key()).is_ok());
+        assert!(
+            verify_signature(&vendor_list, &signature, &signing_key(2).verifying_key()).is_err()
+        );
+
+        // the signature covers the content, not its formatting
+        let reformatted: VendorList = VENDOR_LIST.replace("\n", "").parse().unwrap();
+        assert!(verify_signature(&reformatted, &signature, &key.verifying_key()).is_ok());
+
+        let encoded = encode_signature(&signature);
+        assert_eq!(decode_signature(&encoded).unwrap(), signature);
+        let public_key = encode_verifying_key(&key.verifying_key());
+        assert_eq!(
+            decode_verifying_key(&public_key).unwrap(),
+            key.verifying_key()
+        );
+        assert!(decode_signature(&public_key).is_err());
+    }
+
+    #[test]
+    fn load_verified() {
+        let key = signing_key(1);
+        let signature = encode_signature(&sign(&VENDOR_LIST.parse().unwrap(), &key).unwrap());
+        let tampered = VENDOR_LIST.replace("\"purposeIds\": [1]", "\"purposeIds\": [1, 2]");
+
+        let mut options = IntegrityOptions::default();
+        assert!(load("vendorlist-v8.json", tampered.as_bytes(), None, &options).is_ok());
+
+        options.public_key = Some(key.verifying_key());
+        let good = load(
+            "vendorlist-v8.json",
+            VENDOR_LIST.as_bytes(),
+            Some(&signature),
+            &options,
+        );
+        assert_eq!(good.unwrap().version, 8);
+        assert!(load("vendorlist-v8.json", VENDOR_LIST.as_bytes(), None, &options).is_err());
+        assert!(load(
+            "vendorlist-v8.json",
+            tampered.as_bytes(),
+            Some(&signature),
+            &options
+        )
+        .is_err());
+
+        let mut manifest = Manifest::default();
+        manifest.insert("vendorlist-v8.json", VENDOR_LIST.as_bytes());
+        options.manifest = Some(manifest);
+        assert!(load(
+            "vendorlist-v8.json",
+            VENDOR_LIST.as_bytes(),
+            Some(&signature),
+            &options
+        )
+        .is_ok());
+        // same content, so the signature still holds, but the file differs
+        let reformatted = VENDOR_LIST.replace("\n", "");
+        assert!(load(
+            "vendorlist-v8.json",
+            reformatted.as_bytes(),
+            Some(&signature),
+            &options
+        )
+        .is_err());
+        options.manifest = None;
+        assert!(load(
+            "vendorlist-v8.json",
+            reformatted.as_bytes(),
+            Some(&signature),
+            &options
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn signature_covers_every_field() {
+        let vendor_list = r#"{
+            "gvlSpecificationVersion": 2,
+            "vendorListVersion": 48,
+            "tcfPolicyVersion": 2,
+            "lastUpdated": "2020-07-02T16:00:23Z",
+            "purposes": {
+                "1": { "id": 1, "name": "Store and/or access information on a device", "description": "" }
+            },
+            "specialPurposes": {
+                "1": { "id": 1, "name": "Ensure security, prevent fraud, and debug", "description": "" }
+            },
+            "features": {
+                "1": { "id": 1, "name": "Match and combine offline data sources", "description": "" }
+            },
+            "specialFeatures": {
+                "1": { "id": 1, "name": "Use precise geolocation data", "description": "" }
+            },
+            "stacks": {
+                "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Precise geolocation", "description": "" }
+            },
+            "vendors": {
+                "8": {
+                    "id": 8, "name": "Emerse Sverige AB", "purposes": [1, 3], "legIntPurposes": [2],
+                    "flexiblePurposes": [2], "specialPurposes": [1], "features": [1], "specialFeatures": [1],
+                    "policyUrl": "https://www.emerse.com/privacy-policy/", "usesNonCookieAccess": false,
+                    "overflow": { "httpGetLimit": 32 }, "cookieMaxAgeSeconds": 31536000
+                }
+            }
+        }"#;
+        let key = signing_key(1);
+        let signature = encode_signature(&sign(&vendor_list.parse().unwrap(), &key).unwrap());
+        let options = IntegrityOptions {
+            manifest: None,
+            public_key: Some(key.verifying_key()),
+        };
+        let name = "vendorlist-v48.json";
+        assert!(load(name, vendor_list.as_bytes(), Some(&signature), &options).is_ok());
+        let reformatted = vendor_list.replace("\n", "");
+        assert!(load(name, reformatted.as_bytes(), Some(&signature), &options).is_ok());
+
+        for (from, to) in &[
+            (
+                "\"gvlSpecificationVersion\": 2",
+                "\"gvlSpecificationVersion\": 3",
+            ),
+            ("\"vendorListVersion\": 48", "\"vendorListVersion\": 49"),
+            ("\"tcfPolicyVersion\": 2", "\"tcfPolicyVersion\": 3"),
+            ("2020-07-02T16:00:23Z", "2020-07-03T16:00:23Z"),
+            ("Store and/or access", "Access"),
+            ("Ensure security, prevent fraud", "Ensure security"),
+            ("Match and combine offline", "Combine offline"),
+            ("Use precise geolocation data", "Use geolocation data"),
+            (
+                "\"specialFeatures\": [1], \"name\"",
+                "\"specialFeatures\": [], \"name\"",
+            ),
+            ("Precise geolocation\"", "Geolocation\""),
+            ("\"name\": \"Emerse Sverige AB\"", "\"name\": \"Emerse\""),
+            ("\"purposes\": [1, 3]", "\"purposes\": [1]"),
+            ("\"legIntPurposes\": [2]", "\"legIntPurposes\": [2, 7]"),
+            ("\"flexiblePurposes\": [2]", "\"flexiblePurposes\": []"),
+            ("\"specialPurposes\": [1]", "\"specialPurposes\": [1, 2]"),
+            ("\"features\": [1]", "\"features\": []"),
+            ("\"specialFeatures\": [1],\n", "\"specialFeatures\": [],\n"),
+            ("privacy-policy/", "privacy/"),
+            (
+                "\"usesNonCookieAccess\": false",
+                "\"usesNonCookieAccess\": true",
+            ),
+            ("\"httpGetLimit\": 32", "\"httpGetLimit\": 128"),
+            (
+                "\"cookieMaxAgeSeconds\": 31536000",
+                "\"cookieMaxAgeSeconds\": 0",
+            ),
+        ] {
+            assert_eq!(vendor_list.matches(from).count(), 1, "{}", from);
+            let tampered = vendor_list.replace(from, to);
+            assert!(tampered.parse::<VendorList>().is_ok(), "{}", to);
+            assert!(
+                load(name, tampered.as_bytes(), Some(&signature), &options).is_err(),
+                "{} -> {}",
+                from,
+                to
+            );
+        }
+    }
+}
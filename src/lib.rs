@@ -6,15 +6,69 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+
 extern crate base64;
 extern crate bit_set;
 extern crate bit_vec;
-extern crate bitstream_io;
 extern crate chrono;
+#[cfg(feature = "integrity")]
+extern crate ed25519_dalek;
+#[cfg(feature = "archive")]
+extern crate flate2;
+#[cfg(feature = "snapshot")]
+extern crate memmap2;
+#[cfg(feature = "testing")]
+extern crate proptest;
+#[cfg(feature = "std")]
 extern crate serde;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "std")]
+#[cfg_attr(test, macro_use)]
 extern crate serde_json;
+#[cfg(feature = "integrity")]
+extern crate sha2;
+
+#[cfg(feature = "std")]
+pub mod amp;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[doc(hidden)]
+pub mod bits;
 
+#[cfg(feature = "std")]
+pub mod cmp_api;
+#[cfg(feature = "std")]
+pub mod cmp_list;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod freshness;
+pub mod ids;
+#[cfg(feature = "integrity")]
+pub mod integrity;
+#[cfg(feature = "std")]
+pub mod migrate;
+#[cfg(feature = "std")]
+pub mod openrtb;
+#[cfg(feature = "std")]
+pub mod prebid;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod summary;
+#[cfg(all(test, feature = "std"))]
+mod test_fixtures;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timestamp;
 pub mod vendor_consent;
+#[cfg(feature = "std")]
 pub mod vendor_list;
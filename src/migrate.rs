@@ -0,0 +1,383 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display};
+
+use ids::{FeatureSet, PurposeId, PurposeSet, VendorId, VendorSet};
+use timestamp::Deciseconds;
+use vendor_consent::{V1, V2};
+use vendor_list::VendorList;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationPolicy {
+    // For each v1 purpose, the v2 purposes a v1 consent for it is carried over to.
+    // v1 purposes without an entry are dropped.
+    pub purpose_mapping: HashMap<PurposeId, Vec<PurposeId>>,
+
+    // v2 purposes only carried over when every one of the given v1 purposes was
+    // consented to, for v2 purposes that combine several v1 purposes.
+    pub combined_purposes: Vec<(Vec<PurposeId>, PurposeId)>,
+
+    // Two-letter ISO3166-1 alpha-2 country code of the publisher ("AA" if unknown)
+    pub publisher_cc: String,
+
+    // CMP recorded as having last updated the consent (defaults to the v1 CMP)
+    pub cmp_id: Option<u16>,
+    pub cmp_version: Option<u16>,
+}
+
+impl Default for MigrationPolicy {
+    fn default() -> MigrationPolicy {
+        let mut purpose_mapping = HashMap::new();
+        // Information storage and access
+        purpose_mapping.insert(PurposeId(1), vec![PurposeId(1)]);
+        // Personalisation
+        purpose_mapping.insert(PurposeId(2), vec![PurposeId(3), PurposeId(5)]);
+        // Ad selection, delivery, reporting
+        purpose_mapping.insert(PurposeId(3), vec![PurposeId(2), PurposeId(7)]);
+        // Content selection, delivery, reporting
+        purpose_mapping.insert(PurposeId(4), vec![PurposeId(8)]);
+        // Measurement
+        purpose_mapping.insert(PurposeId(5), vec![PurposeId(9), PurposeId(10)]);
+
+        // Selecting personalised ads or content needs both Personalisation and
+        // the corresponding selection purpose
+        let combined_purposes = vec![
+            (vec![PurposeId(2), PurposeId(3)], PurposeId(4)),
+            (vec![PurposeId(2), PurposeId(4)], PurposeId(6)),
+        ];
+
+        MigrationPolicy {
+            purpose_mapping,
+            combined_purposes,
+            publisher_cc: "AA".to_string(),
+            cmp_id: None,
+            cmp_version: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LossyDecision {
+    // The v1 vendor list does not have the version the consent was given against
+    VendorListVersionMismatch { consent: u16, vendor_list: u16 },
+
+    // Consent for a v1 purpose has no v2 equivalent under the policy
+    UnmappedPurpose(PurposeId),
+
+    // Consent for a v1 purpose was carried over to several v2 purposes
+    PurposeSplit { from: PurposeId, to: Vec<PurposeId> },
+
+    // Consent for several v1 purposes was carried over to a single v2 purpose
+    PurposeCombined { from: Vec<PurposeId>, to: PurposeId },
+
+    // A mapped v2 purpose is not in the v2 vendor list
+    PurposeNotInVendorList(PurposeId),
+
+    // Vendor consent dropped because the vendor is not in the v1 vendor list
+    VendorNotInV1VendorList(VendorId),
+
+    // Vendor consent dropped because the vendor is not in the v2 vendor list
+    // or has been deleted from it
+    VendorNotInV2VendorList(VendorId),
+
+    // v1 has no legitimate interest signals, so none are established
+    LegitimateInterestNotEstablished,
+
+    // v1 has no special feature opt-ins, so none are set
+    SpecialFeaturesNotOptedIn,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Migration {
+    pub consent: V2,
+
+    // Every decision made that lost or reinterpreted part of the v1 consent
+    pub lossy_decisions: Vec<LossyDecision>,
+}
+
+// Converts a v1 consent into a TCF v2 consent given the v1 vendor list it was
+// collected against and the v2 global vendor list to migrate to. Vendors
+// deleted from the v2 vendor list by `now` are not carried over.
+pub fn upgrade(
+    v1: &V1,
+    v1_vendor_list: &VendorList,
+    v2_vendor_list: &VendorList,
+    now: Deciseconds,
+    policy: &MigrationPolicy,
+) -> Result<Migration, Error> {
+    if v1_vendor_list.is_v2() {
+        return Err(Error::Other(format!(
+            "Vendor list version {} is not a v1 vendor list",
+            v1_vendor_list.version
+        )));
+    }
+
+    if !v2_vendor_list.is_v2() {
+        return Err(Error::Other(format!(
+            "Vendor list version {} is not a v2 global vendor list",
+            v2_vendor_list.version
+        )));
+    }
+
+    let mut lossy_decisions = Vec::new();
+
+    if v1.vendor_list_version != v1_vendor_list.version {
+        lossy_decisions.push(LossyDecision::VendorListVersionMismatch {
+            consent: v1.vendor_list_version,
+            vendor_list: v1_vendor_list.version,
+        });
+    }
+
+    let mut purposes_consent = PurposeSet::new();
+    for id in v1.purposes_allowed.iter() {
+        let mapped = match policy.purpose_mapping.get(&id) {
+            Some(mapped) if !mapped.is_empty() => mapped,
+            _ => {
+                lossy_decisions.push(LossyDecision::UnmappedPurpose(id));
+                continue;
+            }
+        };
+
+        if mapped.len() > 1 {
+            lossy_decisions.push(LossyDecision::PurposeSplit {
+                from: id,
+                to: mapped.clone(),
+            });
+        }
+
+        for v2_id in mapped {
+            if v2_id.0 == 0 || !v2_vendor_list.purposes.contains_key(v2_id) {
+                lossy_decisions.push(LossyDecision::PurposeNotInVendorList(*v2_id));
+                continue;
+            }
+            purposes_consent.insert(*v2_id);
+        }
+    }
+
+    for (from, to) in &policy.combined_purposes {
+        if from.is_empty() || !from.iter().all(|id| v1.has_purpose_consent(*id)) {
+            continue;
+        }
+
+        if to.0 == 0 || !v2_vendor_list.purposes.contains_key(to) {
+            lossy_decisions.push(LossyDecision::PurposeNotInVendorList(*to));
+            continue;
+        }
+        lossy_decisions.push(LossyDecision::PurposeCombined {
+            from: from.clone(),
+            to: *to,
+        });
+        purposes_consent.insert(*to);
+    }
+
+    let mut vendor_consent = VendorSet::new();
+    for id in v1.vendor_consent.iter() {
+        if !v1.has_vendor_consent(id) {
+            continue;
+        }
+
+        let in_v2 = v2_vendor_list
+            .vendors
+            .get(&id)
+            .is_some_and(|v| v.is_active_at(now));
+        if !v1_vendor_list.vendors.contains_key(&id) {
+            lossy_decisions.push(LossyDecision::VendorNotInV1VendorList(id));
+        } else if !in_v2 {
+            lossy_decisions.push(LossyDecision::VendorNotInV2VendorList(id));
+        } else {
+            vendor_consent.insert(id);
+        }
+    }
+
+    lossy_decisions.push(LossyDecision::LegitimateInterestNotEstablished);
+    lossy_decisions.push(LossyDecision::SpecialFeaturesNotOptedIn);
+
+    let consent = V2 {
+        created: v1.created,
+        last_updated: v1.last_updated,
+        cmp_id: policy.cmp_id.unwrap_or(v1.cmp_id),
+        cmp_version: policy.cmp_version.unwrap_or(v1.cmp_version),
+        consent_screen: v1.consent_screen,
+        consent_language: v1.consent_language.to_uppercase(),
+        vendor_list_version: v2_vendor_list.version,
+        tcf_policy_version: v2_vendor_list.tcf_policy_version.unwrap_or(2),
+        is_service_specific: false,
+        use_non_standard_stacks: false,
+        special_feature_opt_ins: FeatureSet::new(),
+        purposes_consent,
+        purposes_li_transparency: PurposeSet::new(),
+        purpose_one_treatment: false,
+        publisher_cc: policy.publisher_cc.clone(),
+        max_vendor_id: vendor_consent.max().map_or(0, |id| id.0 as usize),
+        vendor_consent,
+        max_vendor_legitimate_interest_id: 0,
+        vendor_legitimate_interest: VendorSet::new(),
+        publisher_restrictions: Vec::new(),
+        segments: Vec::new(),
+    };
+
+    Ok(Migration {
+        consent,
+        lossy_decisions,
+    })
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Other(String),
+}
+
+impl error::Error for Error {}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_fixtures::{now, v1_vendor_list, V1_CONSENT};
+    use vendor_consent::VendorConsent;
+
+    // Purposes 1-7, with vendor 2 of the v1 vendor list no longer listed
+    fn v2_vendor_list() -> VendorList {
+        r#"{
+            "gvlSpecificationVersion": 2,
+            "vendorListVersion": 48,
+            "tcfPolicyVersion": 2,
+            "lastUpdated": "2020-07-02T16:00:23Z",
+            "purposes": {
+                "1": { "id": 1, "name": "Store and/or access information on a device", "description": "" },
+                "2": { "id": 2, "name": "Select basic ads", "description": "" },
+                "3": { "id": 3, "name": "Create a personalised ads profile", "description": "" },
+                "4": { "id": 4, "name": "Select personalised ads", "description": "" },
+                "5": { "id": 5, "name": "Create a personalised content profile", "description": "" },
+                "6": { "id": 6, "name": "Select personalised content", "description": "" },
+                "7": { "id": 7, "name": "Measure ad performance", "description": "" }
+            },
+            "features": {},
+            "vendors": {
+                "1": { "id": 1, "name": "Kept", "purposes": [1], "legIntPurposes": [], "features": [], "policyUrl": "" },
+                "9": { "id": 9, "name": "Refused", "purposes": [1], "legIntPurposes": [], "features": [], "policyUrl": "" }
+            }
+        }"#
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn upgrade_good() {
+        // vendor 9 refused, all others (up to 2011) consented; purposes 1-3 allowed
+        let v1 = match V1_CONSENT.parse().unwrap() {
+            VendorConsent::V1(v1) => v1,
+            v => panic!("Unexpected consent: {:?}", v),
+        };
+
+        let migration = upgrade(
+            &v1,
+            &v1_vendor_list(),
+            &v2_vendor_list(),
+            now(),
+            &MigrationPolicy::default(),
+        )
+        .unwrap();
+
+        let v2 = &migration.consent;
+        assert_eq!(v2.cmp_id, 7);
+        assert_eq!(v2.consent_language, "EN");
+        assert_eq!(v2.vendor_list_version, 48);
+        assert_eq!(
+            v2.purposes_consent
+                .iter()
+                .map(|id| id.0)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5, 7]
+        );
+        assert!(v2.has_vendor_consent(1));
+        assert!(!v2.has_vendor_consent(2));
+        assert!(!v2.has_vendor_consent(9));
+        assert_eq!(v2.max_vendor_id, 1);
+        assert!(v2.purposes_li_transparency.is_empty());
+        assert!(v2.vendor_legitimate_interest.is_empty());
+
+        let lossy = &migration.lossy_decisions;
+        assert!(lossy.contains(&LossyDecision::PurposeSplit {
+            from: PurposeId(2),
+            to: vec![PurposeId(3), PurposeId(5)]
+        }));
+        assert!(lossy.contains(&LossyDecision::PurposeCombined {
+            from: vec![PurposeId(2), PurposeId(3)],
+            to: PurposeId(4)
+        }));
+        assert!(lossy.contains(&LossyDecision::VendorNotInV2VendorList(VendorId(2))));
+        assert!(lossy.contains(&LossyDecision::VendorNotInV1VendorList(VendorId(3))));
+        assert!(lossy.contains(&LossyDecision::LegitimateInterestNotEstablished));
+        assert!(!lossy.contains(&LossyDecision::UnmappedPurpose(PurposeId(1))));
+
+        // the migrated consent is a valid TCF v2 string
+        let v2 = VendorConsent::V2(migration.consent);
+        assert_eq!(
+            v2.to_string().unwrap().parse::<VendorConsent>().unwrap(),
+            v2
+        );
+    }
+
+    #[test]
+    fn upgrade_without_personalisation() {
+        let mut v1 = match V1_CONSENT.parse().unwrap() {
+            VendorConsent::V1(v1) => v1,
+            v => panic!("Unexpected consent: {:?}", v),
+        };
+        // Ad selection, delivery, reporting only
+        v1.purposes_allowed = [PurposeId(3)].iter().cloned().collect();
+
+        let migration = upgrade(
+            &v1,
+            &v1_vendor_list(),
+            &v2_vendor_list(),
+            now(),
+            &MigrationPolicy::default(),
+        )
+        .unwrap();
+
+        // no Select personalised ads without Personalisation
+        assert_eq!(
+            migration
+                .consent
+                .purposes_consent
+                .iter()
+                .map(|id| id.0)
+                .collect::<Vec<_>>(),
+            vec![2, 7]
+        );
+        assert!(!migration
+            .lossy_decisions
+            .iter()
+            .any(|d| matches!(d, LossyDecision::PurposeCombined { .. })));
+    }
+
+    #[test]
+    fn upgrade_wrong_vendor_list() {
+        let v1 = match V1_CONSENT.parse().unwrap() {
+            VendorConsent::V1(v1) => v1,
+            v => panic!("Unexpected consent: {:?}", v),
+        };
+
+        let policy = MigrationPolicy::default();
+        assert!(upgrade(&v1, &v1_vendor_list(), &v1_vendor_list(), now(), &policy).is_err());
+        assert!(upgrade(&v1, &v2_vendor_list(), &v2_vendor_list(), now(), &policy).is_err());
+    }
+}
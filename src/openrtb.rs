@@ -0,0 +1,514 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serde_json::{self, Value};
+
+use ids::{FeatureId, PurposeId, VendorId};
+use timestamp::Deciseconds;
+use vendor_consent::VendorConsent;
+use vendor_list::{LegalBasis, VendorList};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redaction {
+    // Remove the field at the given dot-separated path (e.g. "user.buyeruid")
+    Remove(String),
+
+    // Zero the last octet of the IPv4 address (or the last 80 bits of the
+    // IPv6 address) at the given dot-separated path
+    TruncateIp(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    // Purpose the target vendor must be allowed to process data for in order
+    // to receive the fields covered by this rule
+    pub purpose_id: Option<PurposeId>,
+
+    // Special feature the target vendor must be allowed to use in order to
+    // receive the fields covered by this rule
+    pub special_feature_id: Option<FeatureId>,
+
+    // Redactions applied when the purpose or special feature is not allowed
+    pub redactions: Vec<Redaction>,
+}
+
+// Default rules, modelled on the TCF v2 policy:
+//
+// - user and device identifiers require Store and/or access information on a
+//   device (purpose 1)
+// - precise geolocation and full IP addresses require the Use precise
+//   geolocation data special feature (1), as the IP address locates the user
+//   as precisely as the coordinates do
+//
+// v1 consent strings have no special features, so for them the location rule
+// only requires vendor consent (see `is_special_feature_allowed`). Callers that
+// map fields to purposes differently can pass their own rules to `redact`.
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule {
+            purpose_id: Some(PurposeId(1)),
+            special_feature_id: None,
+            redactions: vec![
+                Redaction::Remove("user.id".to_string()),
+                Redaction::Remove("user.buyeruid".to_string()),
+                Redaction::Remove("device.ifa".to_string()),
+            ],
+        },
+        Rule {
+            purpose_id: None,
+            special_feature_id: Some(FeatureId(1)),
+            redactions: vec![
+                Redaction::Remove("device.geo.lat".to_string()),
+                Redaction::Remove("device.geo.lon".to_string()),
+                Redaction::Remove("user.geo.lat".to_string()),
+                Redaction::Remove("user.geo.lon".to_string()),
+                Redaction::TruncateIp("device.ip".to_string()),
+                Redaction::TruncateIp("device.ipv6".to_string()),
+            ],
+        },
+    ]
+}
+
+// Whether the vendor may receive data for the given purpose: the vendor must
+// be in the vendor list (and not deleted from it by `now`) and declare the
+// purpose. For TCF v2 consent strings the
+// legal basis follows the vendor declaration and the publisher restrictions:
+// purposes under legitimate interest need the purpose transparency and vendor
+// legitimate interest signals, all others need purpose and vendor consent. v1
+// consent strings only carry consent, so always need purpose and vendor consent.
+pub fn is_allowed<V, P>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    purpose_id: P,
+) -> bool
+where
+    V: Into<VendorId>,
+    P: Into<PurposeId>,
+{
+    let (vendor_id, purpose_id) = (vendor_id.into(), purpose_id.into());
+    let vendor = match vendor_list.vendors.get(&vendor_id) {
+        Some(vendor) if vendor.is_active_at(now) => vendor,
+        _ => return false,
+    };
+
+    let basis = match consent {
+        VendorConsent::V1(_) => {
+            if vendor.purpose_ids.contains(&purpose_id)
+                || vendor.leg_int_purpose_ids.contains(&purpose_id)
+            {
+                LegalBasis::Consent
+            } else {
+                LegalBasis::NotAllowed
+            }
+        }
+        VendorConsent::V2(v2) => vendor.legal_basis(purpose_id, &v2.publisher_restrictions),
+    };
+
+    match (consent, basis) {
+        (_, LegalBasis::NotAllowed) => false,
+        (VendorConsent::V2(v2), LegalBasis::LegitimateInterest) => {
+            v2.purposes_li_transparency.contains(purpose_id)
+                && v2.has_vendor_legitimate_interest(vendor_id)
+        }
+        _ => consent.has_purpose_consent(purpose_id) && consent.has_vendor_consent(vendor_id),
+    }
+}
+
+// Whether the vendor may use the given special feature: for TCF v2 consent
+// strings the vendor must be in the vendor list, declare the special feature,
+// and the user must have opted in to it. v1 consent strings have no special
+// features, so the vendor must be in the vendor list and have been consented to.
+pub fn is_special_feature_allowed<V, F>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    feature_id: F,
+) -> bool
+where
+    V: Into<VendorId>,
+    F: Into<FeatureId>,
+{
+    let (vendor_id, feature_id) = (vendor_id.into(), feature_id.into());
+    let vendor = match vendor_list.vendors.get(&vendor_id) {
+        Some(vendor) if vendor.is_active_at(now) => vendor,
+        _ => return false,
+    };
+
+    match consent {
+        VendorConsent::V1(_) => consent.has_vendor_consent(vendor_id),
+        VendorConsent::V2(_) => {
+            vendor.special_feature_ids.contains(&feature_id)
+                && consent.has_special_feature_opt_in(feature_id)
+        }
+    }
+}
+
+// Returns a copy of the OpenRTB bid request with every rule whose purpose (or
+// special feature) the vendor is not allowed to process applied.
+pub fn redact<V: Into<VendorId>>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    request: &Value,
+    rules: &[Rule],
+) -> Result<Value, Error> {
+    let vendor_id = vendor_id.into();
+    if !request.is_object() {
+        return Err(Error::Other(
+            "Bid request must be a JSON object".to_string(),
+        ));
+    }
+
+    let mut redacted = request.clone();
+    for rule in rules {
+        let purpose_allowed = rule
+            .purpose_id
+            .is_none_or(|purpose_id| is_allowed(consent, vendor_list, now, vendor_id, purpose_id));
+        let special_feature_allowed = rule.special_feature_id.is_none_or(|feature_id| {
+            is_special_feature_allowed(consent, vendor_list, now, vendor_id, feature_id)
+        });
+        if purpose_allowed && special_feature_allowed {
+            continue;
+        }
+
+        for redaction in &rule.redactions {
+            apply(&mut redacted, redaction);
+        }
+    }
+
+    Ok(redacted)
+}
+
+pub fn redact_str<V: Into<VendorId>>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    request: &str,
+    rules: &[Rule],
+) -> Result<String, Error> {
+    let request: Value = serde_json::from_str(request)?;
+    let redacted = redact(consent, vendor_list, now, vendor_id, &request, rules)?;
+    serde_json::to_string(&redacted).map_err(From::from)
+}
+
+// Values at a TruncateIp path that are not IP addresses are removed, since
+// they cannot be truncated and may still identify the user.
+fn apply(request: &mut Value, redaction: &Redaction) {
+    match redaction {
+        Redaction::Remove(path) => {
+            let (parent, key) = split_path(path);
+            if let Some(Value::Object(map)) = lookup(request, parent) {
+                map.remove(key);
+            }
+        }
+        Redaction::TruncateIp(path) => {
+            let (parent, key) = split_path(path);
+            if let Some(Value::Object(map)) = lookup(request, parent) {
+                let truncated = match map.get(key) {
+                    None | Some(Value::Null) => return,
+                    Some(Value::String(ip)) => truncate_ip(ip),
+                    Some(_) => None,
+                };
+                match truncated {
+                    Some(ip) => {
+                        map.insert(key.to_string(), Value::String(ip));
+                    }
+                    None => {
+                        map.remove(key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('.') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    }
+}
+
+fn lookup<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get_mut(key),
+        _ => None,
+    })
+}
+
+fn truncate_ip(ip: &str) -> Option<String> {
+    if let Ok(addr) = ip.parse::<Ipv4Addr>() {
+        let octets = addr.octets();
+        return Some(Ipv4Addr::new(octets[0], octets[1], octets[2], 0).to_string());
+    }
+
+    if let Ok(addr) = ip.parse::<Ipv6Addr>() {
+        let segments = addr.segments();
+        return Some(
+            Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0).to_string(),
+        );
+    }
+
+    None
+}
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::JsonError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::JsonError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ids::{FeatureSet, VendorSet};
+    use test_fixtures::{now, v1_vendor_list, v2_vendor_list, V1_CONSENT};
+    use vendor_consent::V2;
+
+    fn request() -> Value {
+        json!({
+            "id": "req-1",
+            "user": { "id": "u-123", "buyeruid": "b-456", "geo": { "lat": 52.52, "lon": 13.40, "country": "DEU" } },
+            "device": { "ifa": "ifa-789", "ip": "192.168.1.37", "ipv6": "2001:db8:85a3::8a2e:370:7334" }
+        })
+    }
+
+    // v2 consent for vendor 1 of the v2 vendor list only
+    fn v2_consent(
+        special_feature_opt_ins: FeatureSet,
+        purposes_consent: &[u8],
+        purposes_li_transparency: &[u8],
+        vendor_consent: bool,
+    ) -> VendorConsent {
+        let vendor_1: VendorSet = [VendorId(1)].iter().cloned().collect();
+        VendorConsent::V2(V2 {
+            created: "2020-02-21T00:00:00Z".parse().unwrap(),
+            last_updated: "2020-02-21T00:00:00Z".parse().unwrap(),
+            cmp_id: 300,
+            cmp_version: 2,
+            consent_screen: 1,
+            consent_language: "EN".to_string(),
+            vendor_list_version: 15,
+            tcf_policy_version: 2,
+            is_service_specific: false,
+            use_non_standard_stacks: false,
+            special_feature_opt_ins,
+            purposes_consent: purposes_consent.iter().cloned().map(PurposeId).collect(),
+            purposes_li_transparency: purposes_li_transparency
+                .iter()
+                .cloned()
+                .map(PurposeId)
+                .collect(),
+            purpose_one_treatment: false,
+            publisher_cc: "AA".to_string(),
+            max_vendor_id: 1,
+            vendor_consent: if vendor_consent {
+                vendor_1.clone()
+            } else {
+                VendorSet::new()
+            },
+            max_vendor_legitimate_interest_id: 1,
+            vendor_legitimate_interest: vendor_1,
+            publisher_restrictions: vec![],
+            segments: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn redact_consenting_vendor() {
+        let consent = V1_CONSENT.parse().unwrap();
+
+        let redacted = redact(
+            &consent,
+            &v1_vendor_list(),
+            now(),
+            1,
+            &request(),
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(redacted, request());
+    }
+
+    #[test]
+    fn redact_refused_vendor() {
+        let consent = V1_CONSENT.parse().unwrap();
+
+        let redacted = redact(
+            &consent,
+            &v1_vendor_list(),
+            now(),
+            9,
+            &request(),
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(
+            redacted,
+            json!({
+                "id": "req-1",
+                "user": { "geo": { "country": "DEU" } },
+                "device": { "ip": "192.168.1.0", "ipv6": "2001:db8:85a3::" }
+            })
+        );
+    }
+
+    #[test]
+    fn redact_precise_geo_without_opt_in() {
+        let vendor_list = v2_vendor_list();
+
+        let opted_in = v2_consent([FeatureId(1)].iter().cloned().collect(), &[1], &[], true);
+        let redacted = redact(
+            &opted_in,
+            &vendor_list,
+            now(),
+            1,
+            &request(),
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(redacted, request());
+
+        let redacted = redact(
+            &v2_consent(FeatureSet::new(), &[1], &[], true),
+            &vendor_list,
+            now(),
+            1,
+            &request(),
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(
+            redacted["user"],
+            json!({ "id": "u-123", "buyeruid": "b-456", "geo": { "country": "DEU" } })
+        );
+        assert_eq!(
+            redacted["device"],
+            json!({ "ifa": "ifa-789", "ip": "192.168.1.0", "ipv6": "2001:db8:85a3::" })
+        );
+    }
+
+    #[test]
+    fn is_allowed_legitimate_interest() {
+        let vendor_list = v2_vendor_list();
+        let allowed = |consent: VendorConsent, purpose_id: u8| {
+            is_allowed(&consent, &vendor_list, now(), 1, purpose_id)
+        };
+
+        // purpose 7 is declared under legitimate interest, so consent to it is not enough
+        assert!(allowed(v2_consent(FeatureSet::new(), &[], &[7], false), 7));
+        assert!(!allowed(v2_consent(FeatureSet::new(), &[7], &[], true), 7));
+        // purpose 1 needs consent, which the vendor does not have
+        assert!(!allowed(
+            v2_consent(FeatureSet::new(), &[1], &[1], false),
+            1
+        ));
+    }
+
+    #[test]
+    fn redact_unknown_vendor() {
+        let consent = V1_CONSENT.parse().unwrap();
+
+        let redacted = redact_str(
+            &consent,
+            &v1_vendor_list(),
+            now(),
+            32,
+            r#"{"user":{"id":"u-123"},"device":{"ip":"10.1.2.3"}}"#,
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(redacted, r#"{"device":{"ip":"10.1.2.0"},"user":{}}"#);
+    }
+
+    #[test]
+    fn redact_invalid_ip() {
+        let consent = V1_CONSENT.parse().unwrap();
+        let request = json!({
+            "device": { "ifa": "ifa-789", "ip": "not an ip", "ipv6": 42 },
+            "user": { "geo": { "lat": 52.52 } }
+        });
+
+        let redacted = redact(
+            &consent,
+            &v1_vendor_list(),
+            now(),
+            VendorId(9),
+            &request,
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(redacted, json!({ "device": {}, "user": { "geo": {} } }));
+    }
+
+    #[test]
+    fn redact_deleted_vendor() {
+        let consent = V1_CONSENT.parse().unwrap();
+        let mut vendor_list = v1_vendor_list();
+        vendor_list.vendors.get_mut(&1).unwrap().deleted_date =
+            Some("2018-06-01T00:00:00Z".parse().unwrap());
+
+        assert!(is_allowed(
+            &consent,
+            &vendor_list,
+            "2018-05-01T00:00:00Z".parse().unwrap(),
+            1,
+            1
+        ));
+        assert!(!is_allowed(&consent, &vendor_list, now(), 1, 1));
+        let redacted = redact(
+            &consent,
+            &vendor_list,
+            now(),
+            1,
+            &request(),
+            &default_rules(),
+        )
+        .unwrap();
+        assert_eq!(redacted["user"], json!({ "geo": { "country": "DEU" } }));
+    }
+}
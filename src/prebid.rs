@@ -0,0 +1,523 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// The gdprConsent object Prebid.js passes to bidders, and the checks of its
+// TCF 2 enforcement module: whether a bidder may sync users (purpose 1),
+// receive bid requests (purpose 2) or have its bids measured (purpose 7).
+
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde_json::{self, Map, Value};
+
+use cmp_api::{self, ConsentData, EventStatus, TcData, VendorConsents};
+use ids::{PurposeId, VendorId};
+use timestamp::Deciseconds;
+use vendor_consent::{self, VendorConsent};
+use vendor_list::{LegalBasis, VendorList};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GdprConsent {
+    #[serde(
+        rename = "consentString",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub consent_string: Option<String>,
+
+    // Missing when the CMP could not tell; treated as applying
+    #[serde(
+        rename = "gdprApplies",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub gdpr_applies: Option<bool>,
+
+    // What the CMP returned: a TCData for TCF v2, getConsentData and
+    // getVendorConsents responses for TCF v1
+    #[serde(
+        rename = "vendorData",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vendor_data: Option<Value>,
+
+    // TCF version of the CMP API, 0 when no CMP was found
+    #[serde(rename = "apiVersion", default)]
+    pub api_version: u8,
+
+    // Fields this crate does not model (e.g. addtlConsent)
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// vendorData for TCF v1 CMPs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1VendorData {
+    #[serde(rename = "getConsentData")]
+    pub consent_data: ConsentData,
+
+    #[serde(rename = "getVendorConsents")]
+    pub vendor_consents: VendorConsents,
+}
+
+impl GdprConsent {
+    // The object Prebid.js would build from a CMP holding the consent string,
+    // with vendorData covering every vendor in the vendor list as of `now`.
+    // The consent string is passed through unchanged.
+    pub fn new(
+        consent_string: &str,
+        vendor_list: &VendorList,
+        now: Deciseconds,
+        gdpr_applies: bool,
+    ) -> Result<GdprConsent, Error> {
+        let (api_version, vendor_data) = match consent_string.parse()? {
+            VendorConsent::V1(v1) => {
+                let vendor_data = V1VendorData {
+                    consent_data: ConsentData::new(consent_string, gdpr_applies)?,
+                    vendor_consents: VendorConsents::new(
+                        &v1,
+                        vendor_list,
+                        now,
+                        None,
+                        gdpr_applies,
+                    )?,
+                };
+                (1, serde_json::to_value(vendor_data)?)
+            }
+            VendorConsent::V2(_) => {
+                let tc_data = TcData::new(
+                    consent_string,
+                    vendor_list,
+                    now,
+                    gdpr_applies,
+                    EventStatus::TcLoaded,
+                )?;
+                (2, serde_json::to_value(tc_data)?)
+            }
+        };
+
+        Ok(GdprConsent {
+            consent_string: Some(consent_string.to_string()),
+            gdpr_applies: Some(gdpr_applies),
+            vendor_data: Some(vendor_data),
+            api_version,
+            extra: Map::new(),
+        })
+    }
+
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+
+    pub fn gdpr_applies(&self) -> bool {
+        self.gdpr_applies.unwrap_or(true)
+    }
+
+    // Decodes the consent string, if any.
+    pub fn decode(&self) -> Result<Option<VendorConsent>, Error> {
+        match self.consent_string {
+            Some(ref s) if !s.is_empty() => Ok(Some(s.parse()?)),
+            _ => Ok(None),
+        }
+    }
+
+    // Whether the rule for the purpose lets the vendor through. Always true
+    // where GDPR does not apply, always false where it does but there is no
+    // consent string.
+    pub fn is_allowed<V, P>(
+        &self,
+        vendor_list: &VendorList,
+        now: Deciseconds,
+        vendor_id: V,
+        purpose_id: P,
+        rules: &[Rule],
+    ) -> Result<bool, Error>
+    where
+        V: Into<VendorId>,
+        P: Into<PurposeId>,
+    {
+        if !self.gdpr_applies() {
+            return Ok(true);
+        }
+        Ok(match self.decode()? {
+            Some(consent) => is_allowed(&consent, vendor_list, now, vendor_id, purpose_id, rules),
+            None => false,
+        })
+    }
+
+    pub fn is_user_sync_allowed<V: Into<VendorId>>(
+        &self,
+        vendor_list: &VendorList,
+        now: Deciseconds,
+        vendor_id: V,
+        rules: &[Rule],
+    ) -> Result<bool, Error> {
+        self.is_allowed(vendor_list, now, vendor_id, STORAGE, rules)
+    }
+}
+
+impl FromStr for GdprConsent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(From::from)
+    }
+}
+
+// Purposes covered by the enforcement module's default rules
+pub const STORAGE: PurposeId = PurposeId(1);
+pub const BASIC_ADS: PurposeId = PurposeId(2);
+pub const MEASUREMENT: PurposeId = PurposeId(7);
+
+// One entry of the enforcement module's rules configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub purpose_id: PurposeId,
+
+    // Require the user's consent (or legitimate interest transparency) for
+    // the purpose
+    pub enforce_purpose: bool,
+
+    // Require the vendor to be in the vendor list and have the user's consent
+    // (or legitimate interest) for it
+    pub enforce_vendor: bool,
+
+    // Vendors the rule does not apply to
+    pub vendor_exceptions: Vec<VendorId>,
+}
+
+impl Rule {
+    pub fn new<P: Into<PurposeId>>(purpose_id: P) -> Rule {
+        Rule {
+            purpose_id: purpose_id.into(),
+            enforce_purpose: true,
+            enforce_vendor: true,
+            vendor_exceptions: Vec::new(),
+        }
+    }
+}
+
+// The module's defaults: purposes 1, 2 and 7 fully enforced.
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule::new(STORAGE),
+        Rule::new(BASIC_ADS),
+        Rule::new(MEASUREMENT),
+    ]
+}
+
+// Whether the vendor passes the rule for the purpose. Purposes without a rule
+// are not enforced. Vendors deleted from the vendor list by `now` count as
+// missing from it.
+//
+// For TCF v2 consent strings the vendor must have the user's consent for the
+// purpose, or legitimate interest where it declared it, once publisher
+// restrictions are applied. v1 consent strings only carry consent.
+pub fn is_allowed<V, P>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    purpose_id: P,
+    rules: &[Rule],
+) -> bool
+where
+    V: Into<VendorId>,
+    P: Into<PurposeId>,
+{
+    let (vendor_id, purpose_id) = (vendor_id.into(), purpose_id.into());
+    let rule = match rules.iter().find(|r| r.purpose_id == purpose_id) {
+        Some(rule) => rule,
+        None => return true,
+    };
+    if rule.vendor_exceptions.contains(&vendor_id) {
+        return true;
+    }
+
+    let vendor = vendor_list
+        .vendors
+        .get(&vendor_id)
+        .filter(|vendor| vendor.is_active_at(now));
+    if rule.enforce_vendor && vendor.is_none() {
+        return false;
+    }
+
+    let basis = match (consent, vendor) {
+        (VendorConsent::V2(v2), Some(vendor)) => {
+            vendor.legal_basis(purpose_id, &v2.publisher_restrictions)
+        }
+        _ => LegalBasis::Consent,
+    };
+
+    let (purpose_ok, vendor_ok) = match (consent, basis) {
+        (_, LegalBasis::NotAllowed) => return false,
+        (VendorConsent::V2(v2), LegalBasis::LegitimateInterest) => (
+            v2.purposes_li_transparency.contains(purpose_id),
+            v2.has_vendor_legitimate_interest(vendor_id),
+        ),
+        _ => (
+            consent.has_purpose_consent(purpose_id),
+            consent.has_vendor_consent(vendor_id),
+        ),
+    };
+
+    (purpose_ok || !rule.enforce_purpose) && (vendor_ok || !rule.enforce_vendor)
+}
+
+pub fn is_user_sync_allowed<V: Into<VendorId>>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    rules: &[Rule],
+) -> bool {
+    is_allowed(consent, vendor_list, now, vendor_id, STORAGE, rules)
+}
+
+pub fn is_bid_request_allowed<V: Into<VendorId>>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    rules: &[Rule],
+) -> bool {
+    is_allowed(consent, vendor_list, now, vendor_id, BASIC_ADS, rules)
+}
+
+pub fn is_measurement_allowed<V: Into<VendorId>>(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    vendor_id: V,
+    rules: &[Rule],
+) -> bool {
+    is_allowed(consent, vendor_list, now, vendor_id, MEASUREMENT, rules)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    JsonError(serde_json::Error),
+    VendorConsentError(vendor_consent::Error),
+    CmpApiError(cmp_api::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::JsonError(ref err) => Some(err),
+            Error::VendorConsentError(ref err) => Some(err),
+            Error::CmpApiError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::JsonError(ref err) => Display::fmt(err, f),
+            Error::VendorConsentError(ref err) => Display::fmt(err, f),
+            Error::CmpApiError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
+impl From<vendor_consent::Error> for Error {
+    fn from(e: vendor_consent::Error) -> Error {
+        Error::VendorConsentError(e)
+    }
+}
+
+impl From<cmp_api::Error> for Error {
+    fn from(e: cmp_api::Error) -> Error {
+        Error::CmpApiError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_fixtures::{now, v1_vendor_list, v2_vendor_list, V1_CONSENT, V2_CONSENT};
+
+    #[test]
+    fn enforcement() {
+        // purposes 1-3 consented, vendors 2, 6 and 8 consented and under
+        // legitimate interest
+        let consent: VendorConsent = V2_CONSENT.parse().unwrap();
+        let vendor_list = v2_vendor_list();
+        let rules = default_rules();
+
+        assert!(is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            2,
+            &rules
+        ));
+        assert!(is_bid_request_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            2,
+            &rules
+        ));
+        // legitimate interest, but without transparency for purpose 7
+        assert!(!is_measurement_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            2,
+            &rules
+        ));
+        // no vendor consent
+        assert!(!is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            3,
+            &rules
+        ));
+        // purpose 1 not declared
+        assert!(!is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            6,
+            &rules
+        ));
+        assert!(is_bid_request_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            6,
+            &rules
+        ));
+        // deleted from the vendor list, or not in it
+        let before_deletion = "2020-05-01T00:00:00Z".parse().unwrap();
+        assert!(is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            before_deletion,
+            8,
+            &rules
+        ));
+        assert!(!is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            8,
+            &rules
+        ));
+        assert!(!is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            9,
+            &rules
+        ));
+        // purpose 3 is not enforced
+        assert!(is_allowed(&consent, &vendor_list, now(), 8, 3, &rules));
+
+        let relaxed = vec![
+            Rule {
+                enforce_vendor: false,
+                vendor_exceptions: vec![VendorId(3)],
+                ..Rule::new(STORAGE)
+            },
+            Rule {
+                enforce_purpose: false,
+                ..Rule::new(MEASUREMENT)
+            },
+        ];
+        assert!(is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            3,
+            &relaxed
+        ));
+        assert!(is_user_sync_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            8,
+            &relaxed
+        ));
+        assert!(is_measurement_allowed(
+            &consent,
+            &vendor_list,
+            now(),
+            2,
+            &relaxed
+        ));
+    }
+
+    #[test]
+    fn gdpr_consent() {
+        let gdpr_consent: GdprConsent = r#"{
+            "consentString": "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA",
+            "gdprApplies": true,
+            "vendorData": { "cmpId": 27, "eventStatus": "tcloaded" },
+            "apiVersion": 2,
+            "addtlConsent": "1~7.12"
+        }"#
+        .parse()
+        .unwrap();
+        assert_eq!(gdpr_consent.api_version, 2);
+        assert!(gdpr_consent.extra.contains_key("addtlConsent"));
+
+        let vendor_list = v2_vendor_list();
+        let rules = default_rules();
+        assert!(gdpr_consent
+            .is_user_sync_allowed(&vendor_list, now(), 2, &rules)
+            .unwrap());
+        assert!(!gdpr_consent
+            .is_user_sync_allowed(&vendor_list, now(), 3, &rules)
+            .unwrap());
+
+        let no_cmp: GdprConsent = r#"{"gdprApplies":true}"#.parse().unwrap();
+        assert_eq!(no_cmp.api_version, 0);
+        assert!(!no_cmp
+            .is_user_sync_allowed(&vendor_list, now(), 2, &rules)
+            .unwrap());
+        let not_applicable: GdprConsent = r#"{"gdprApplies":false}"#.parse().unwrap();
+        assert!(not_applicable
+            .is_user_sync_allowed(&vendor_list, now(), 3, &rules)
+            .unwrap());
+    }
+
+    #[test]
+    fn from_consent() {
+        let v2_string = format!("{}.YAAAAAAAAAAA", V2_CONSENT);
+        let v2 = GdprConsent::new(&v2_string, &v2_vendor_list(), now(), true).unwrap();
+        assert_eq!(v2.api_version, 2);
+        assert_eq!(v2.consent_string.as_ref().unwrap(), &v2_string);
+        let tc_data: TcData = serde_json::from_value(v2.vendor_data.clone().unwrap()).unwrap();
+        assert_eq!(tc_data.tc_string, v2_string);
+        assert_eq!(v2.to_string().unwrap().parse::<GdprConsent>().unwrap(), v2);
+
+        let v1 = GdprConsent::new(V1_CONSENT, &v1_vendor_list(), now(), false).unwrap();
+        assert_eq!(v1.api_version, 1);
+        assert_eq!(v1.gdpr_applies, Some(false));
+        let vendor_data: V1VendorData = serde_json::from_value(v1.vendor_data.unwrap()).unwrap();
+        assert_eq!(vendor_data.consent_data.consent_data, V1_CONSENT);
+        assert_eq!(vendor_data.vendor_consents.vendor_consents.len(), 3);
+    }
+}
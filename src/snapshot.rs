@@ -0,0 +1,717 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Compact binary snapshots of vendor lists, prebuilt from the JSON form so
+// services can load them without parsing JSON.
+//
+// Layout (integers little-endian, strings and lists length-prefixed, entries
+// in ID order):
+//
+//   "GVLS", format version, strip flags
+//   header: GVL spec version, list version, TCF policy version, last updated
+//   purposes, features, special purposes, special features
+//   vendors, stacks
+//   translations: language, purposes, features, special purposes, special
+//   features, stacks
+//   unmodeled top-level fields as JSON
+
+use std::collections::HashMap;
+use std::convert::From;
+use std::error;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::str;
+
+use chrono::{DateTime, TimeZone, Utc};
+use memmap2::Mmap;
+use serde_json::{self, Map, Value};
+
+use ids::{FeatureId, Id, PurposeId, StackId, VendorId};
+use vendor_list::{self, Feature, Overflow, Purpose, Stack, Translations, Vendor, VendorList};
+
+const MAGIC: &[u8; 4] = b"GVLS";
+const FORMAT_VERSION: u8 = 1;
+
+const STRIP_POLICY_URLS: u8 = 1;
+const STRIP_DESCRIPTIONS: u8 = 1 << 1;
+const STRIP_EXTRA: u8 = 1 << 2;
+
+// Fields left out of a snapshot. Stripped strings load as empty strings and
+// stripped unmodeled fields as empty maps.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StripOptions {
+    pub policy_urls: bool,
+    // Purpose, feature and stack descriptions, including translations
+    pub descriptions: bool,
+    // Fields this crate does not model
+    pub extra: bool,
+}
+
+impl StripOptions {
+    // Everything but IDs, names and declarations
+    pub fn all() -> StripOptions {
+        StripOptions {
+            policy_urls: true,
+            descriptions: true,
+            extra: true,
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.policy_urls {
+            flags |= STRIP_POLICY_URLS;
+        }
+        if self.descriptions {
+            flags |= STRIP_DESCRIPTIONS;
+        }
+        if self.extra {
+            flags |= STRIP_EXTRA;
+        }
+        flags
+    }
+
+    fn from_flags(flags: u8) -> StripOptions {
+        StripOptions {
+            policy_urls: flags & STRIP_POLICY_URLS != 0,
+            descriptions: flags & STRIP_DESCRIPTIONS != 0,
+            extra: flags & STRIP_EXTRA != 0,
+        }
+    }
+}
+
+pub fn encode(vendor_list: &VendorList, strip: &StripOptions) -> Result<Vec<u8>, Error> {
+    let mut w = Writer {
+        buf: Vec::new(),
+        strip: *strip,
+    };
+    w.buf.extend_from_slice(MAGIC);
+    w.u8(FORMAT_VERSION);
+    w.u8(strip.flags());
+
+    w.u8(vendor_list.gvl_specification_version.unwrap_or(0));
+    w.u16(vendor_list.version);
+    w.u8(vendor_list.tcf_policy_version.unwrap_or(0));
+    w.datetime(&vendor_list.last_updated);
+
+    w.purposes(&vendor_list.purposes)?;
+    w.features(&vendor_list.features)?;
+    w.purposes(&vendor_list.special_purposes)?;
+    w.features(&vendor_list.special_features)?;
+
+    let mut vendors: Vec<&Vendor> = vendor_list.vendors.values().collect();
+    vendors.sort_by_key(|v| v.id);
+    w.len(vendors.len())?;
+    for vendor in vendors {
+        w.vendor(vendor)?;
+    }
+
+    w.stacks(&vendor_list.stacks)?;
+
+    let mut languages: Vec<&String> = vendor_list.translations.keys().collect();
+    languages.sort();
+    w.len(languages.len())?;
+    for language in languages {
+        let translations = &vendor_list.translations[language];
+        w.str(language)?;
+        w.purposes(&translations.purposes)?;
+        w.features(&translations.features)?;
+        w.purposes(&translations.special_purposes)?;
+        w.features(&translations.special_features)?;
+        w.stacks(&translations.stacks)?;
+    }
+
+    w.extra(&vendor_list.extra)?;
+    Ok(w.buf)
+}
+
+pub fn decode(data: &[u8]) -> Result<VendorList, Error> {
+    let mut r = Reader { data, pos: 0 };
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err(Error::Other("Not a vendor list snapshot".to_string()));
+    }
+    let format_version = r.u8()?;
+    if format_version != FORMAT_VERSION {
+        return Err(Error::Other(format!(
+            "Unsupported snapshot format version {}",
+            format_version
+        )));
+    }
+    r.u8()?; // strip flags, see strip_options()
+
+    let gvl_specification_version = Some(r.u8()?).filter(|v| *v != 0);
+    let version = r.u16()?;
+    let tcf_policy_version = Some(r.u8()?).filter(|v| *v != 0);
+    let last_updated = r.datetime()?;
+
+    let purposes = r.purposes()?;
+    let features = r.features()?;
+    let special_purposes = r.purposes()?;
+    let special_features = r.features()?;
+
+    let mut vendors = HashMap::new();
+    for _ in 0..r.len()? {
+        let vendor = r.vendor()?;
+        vendors.insert(vendor.id, vendor);
+    }
+
+    let stacks = r.stacks()?;
+
+    let mut translations = HashMap::new();
+    for _ in 0..r.len()? {
+        let language = r.string()?;
+        let t = Translations {
+            purposes: r.purposes()?,
+            features: r.features()?,
+            special_purposes: r.purposes()?,
+            special_features: r.features()?,
+            stacks: r.stacks()?,
+        };
+        translations.insert(language, t);
+    }
+
+    let extra = r.extra()?;
+    if r.pos != data.len() {
+        return Err(Error::Other(format!(
+            "{} unexpected bytes after snapshot",
+            data.len() - r.pos
+        )));
+    }
+
+    Ok(VendorList {
+        gvl_specification_version,
+        version,
+        tcf_policy_version,
+        last_updated,
+        purposes,
+        features,
+        special_purposes,
+        special_features,
+        vendors,
+        stacks,
+        translations,
+        extra,
+        index: Default::default(),
+    })
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<VendorList, Error> {
+    decode(&fs::read(path)?)
+}
+
+/// Loads a snapshot by memory-mapping the file instead of reading it into a
+/// buffer first.
+///
+/// # Safety
+///
+/// The file must not be truncated or written to while it is loading: the
+/// mapping is decoded as a byte slice, and changing it underneath is undefined
+/// behaviour. Deploy snapshots by renaming a new file over the old one rather
+/// than rewriting it in place. Callers that cannot rule this out should use
+/// `load`, or map the file themselves and pass the bytes to `decode`.
+pub unsafe fn load_mmap<P: AsRef<Path>>(path: P) -> Result<VendorList, Error> {
+    let file = File::open(path)?;
+    let map = Mmap::map(&file)?;
+    decode(&map)
+}
+
+// Converts a vendor list in the IAB JSON form to a snapshot.
+pub fn from_json(json: &str, strip: &StripOptions) -> Result<Vec<u8>, Error> {
+    let vendor_list: VendorList = json.parse()?;
+    encode(&vendor_list, strip)
+}
+
+// What was stripped from a snapshot when it was built.
+pub fn strip_options(data: &[u8]) -> Result<StripOptions, Error> {
+    if data.len() < MAGIC.len() + 2 || &data[..MAGIC.len()] != MAGIC {
+        return Err(Error::Other("Not a vendor list snapshot".to_string()));
+    }
+    Ok(StripOptions::from_flags(data[MAGIC.len() + 1]))
+}
+
+struct Writer {
+    buf: Vec<u8>,
+    strip: StripOptions,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) -> Result<(), Error> {
+        if len > u32::MAX as usize {
+            return Err(Error::Other(format!(
+                "{} entries do not fit in a snapshot",
+                len
+            )));
+        }
+        self.buf.extend_from_slice(&(len as u32).to_le_bytes());
+        Ok(())
+    }
+
+    fn str(&mut self, s: &str) -> Result<(), Error> {
+        self.len(s.len())?;
+        self.buf.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn description(&mut self, s: &str) -> Result<(), Error> {
+        self.str(if self.strip.descriptions { "" } else { s })
+    }
+
+    // Unmodeled fields as a JSON object, or nothing if there are none
+    fn extra(&mut self, extra: &Map<String, Value>) -> Result<(), Error> {
+        if self.strip.extra || extra.is_empty() {
+            return self.str("");
+        }
+        let json = serde_json::to_string(extra).map_err(vendor_list::Error::from)?;
+        self.str(&json)
+    }
+
+    fn datetime(&mut self, t: &DateTime<Utc>) {
+        self.buf.extend_from_slice(&t.timestamp().to_le_bytes());
+        self.buf
+            .extend_from_slice(&t.timestamp_subsec_nanos().to_le_bytes());
+    }
+
+    // IDs of at most 16 bits
+    fn ids<T: Id>(&mut self, ids: &[T]) -> Result<(), Error> {
+        self.len(ids.len())?;
+        for id in ids {
+            let index = id
+                .index()
+                .ok_or_else(|| Error::Other("ID 0 in vendor list".to_string()))?;
+            self.u16(index as u16);
+        }
+        Ok(())
+    }
+
+    fn purposes(&mut self, purposes: &HashMap<PurposeId, Purpose>) -> Result<(), Error> {
+        let mut purposes: Vec<&Purpose> = purposes.values().collect();
+        purposes.sort_by_key(|p| p.id);
+        self.len(purposes.len())?;
+        for p in purposes {
+            self.u8(p.id.0);
+            self.str(&p.name)?;
+            self.description(&p.description)?;
+            self.extra(&p.extra)?;
+        }
+        Ok(())
+    }
+
+    fn features(&mut self, features: &HashMap<FeatureId, Feature>) -> Result<(), Error> {
+        let mut features: Vec<&Feature> = features.values().collect();
+        features.sort_by_key(|f| f.id);
+        self.len(features.len())?;
+        for f in features {
+            self.u8(f.id.0);
+            self.str(&f.name)?;
+            self.description(&f.description)?;
+            self.extra(&f.extra)?;
+        }
+        Ok(())
+    }
+
+    fn stacks(&mut self, stacks: &HashMap<StackId, Stack>) -> Result<(), Error> {
+        let mut stacks: Vec<&Stack> = stacks.values().collect();
+        stacks.sort_by_key(|s| s.id);
+        self.len(stacks.len())?;
+        for s in stacks {
+            self.u8(s.id.0);
+            self.ids(&s.purpose_ids)?;
+            self.ids(&s.special_feature_ids)?;
+            self.str(&s.name)?;
+            self.description(&s.description)?;
+            self.extra(&s.extra)?;
+        }
+        Ok(())
+    }
+
+    fn vendor(&mut self, vendor: &Vendor) -> Result<(), Error> {
+        self.u16(vendor.id.0);
+        self.str(&vendor.name)?;
+        self.str(if self.strip.policy_urls {
+            ""
+        } else {
+            &vendor.policy_url
+        })?;
+        self.ids(&vendor.purpose_ids)?;
+        self.ids(&vendor.leg_int_purpose_ids)?;
+        self.ids(&vendor.flexible_purpose_ids)?;
+        self.ids(&vendor.feature_ids)?;
+        self.ids(&vendor.special_purpose_ids)?;
+        self.ids(&vendor.special_feature_ids)?;
+
+        self.u8(match vendor.uses_non_cookie_access {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        });
+        match vendor.deleted_date {
+            Some(ref t) => {
+                self.u8(1);
+                self.datetime(t);
+            }
+            None => self.u8(0),
+        }
+        match vendor.overflow {
+            Some(ref overflow) => {
+                self.u8(1);
+                self.u16(overflow.http_get_limit);
+            }
+            None => self.u8(0),
+        }
+
+        self.extra(&vendor.extra)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| Error::Other("Snapshot is truncated".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.take(N)?);
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.len()?;
+        str::from_utf8(self.take(len)?)
+            .map_err(|e| Error::Other(format!("Invalid string in snapshot: {}", e)))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.str().map(String::from)
+    }
+
+    fn extra(&mut self) -> Result<Map<String, Value>, Error> {
+        match self.str()? {
+            "" => Ok(Map::new()),
+            json => serde_json::from_str(json)
+                .map_err(|e| Error::VendorListError(vendor_list::Error::from(e))),
+        }
+    }
+
+    fn datetime(&mut self) -> Result<DateTime<Utc>, Error> {
+        let secs = i64::from_le_bytes(self.array()?);
+        let nanos = u32::from_le_bytes(self.array()?);
+        Utc.timestamp_opt(secs, nanos).single().ok_or_else(|| {
+            Error::Other(format!("Invalid timestamp {}.{} in snapshot", secs, nanos))
+        })
+    }
+
+    fn ids<T: Id>(&mut self) -> Result<Vec<T>, Error> {
+        let len = self.len()?;
+        let mut ids = Vec::with_capacity(len.min(self.data.len()));
+        for _ in 0..len {
+            let index = self.u16()? as usize;
+            let id = T::from_index(index)
+                .ok_or_else(|| Error::Other(format!("Invalid ID index {} in snapshot", index)))?;
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    fn purposes(&mut self) -> Result<HashMap<PurposeId, Purpose>, Error> {
+        let mut purposes = HashMap::new();
+        for _ in 0..self.len()? {
+            let p = Purpose {
+                id: PurposeId(self.u8()?),
+                name: self.string()?,
+                description: self.string()?,
+                extra: self.extra()?,
+            };
+            purposes.insert(p.id, p);
+        }
+        Ok(purposes)
+    }
+
+    fn features(&mut self) -> Result<HashMap<FeatureId, Feature>, Error> {
+        let mut features = HashMap::new();
+        for _ in 0..self.len()? {
+            let f = Feature {
+                id: FeatureId(self.u8()?),
+                name: self.string()?,
+                description: self.string()?,
+                extra: self.extra()?,
+            };
+            features.insert(f.id, f);
+        }
+        Ok(features)
+    }
+
+    fn stacks(&mut self) -> Result<HashMap<StackId, Stack>, Error> {
+        let mut stacks = HashMap::new();
+        for _ in 0..self.len()? {
+            let s = Stack {
+                id: StackId(self.u8()?),
+                purpose_ids: self.ids()?,
+                special_feature_ids: self.ids()?,
+                name: self.string()?,
+                description: self.string()?,
+                extra: self.extra()?,
+            };
+            stacks.insert(s.id, s);
+        }
+        Ok(stacks)
+    }
+
+    fn vendor(&mut self) -> Result<Vendor, Error> {
+        Ok(Vendor {
+            id: VendorId(self.u16()?),
+            name: self.string()?,
+            policy_url: self.string()?,
+            purpose_ids: self.ids()?,
+            leg_int_purpose_ids: self.ids()?,
+            flexible_purpose_ids: self.ids()?,
+            feature_ids: self.ids()?,
+            special_purpose_ids: self.ids()?,
+            special_feature_ids: self.ids()?,
+            uses_non_cookie_access: match self.u8()? {
+                0 => None,
+                1 => Some(false),
+                _ => Some(true),
+            },
+            deleted_date: match self.u8()? {
+                0 => None,
+                _ => Some(self.datetime()?),
+            },
+            overflow: match self.u8()? {
+                0 => None,
+                _ => Some(Overflow {
+                    http_get_limit: self.u16()?,
+                }),
+            },
+            extra: self.extra()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(io::Error),
+    VendorListError(vendor_list::Error),
+    Other(String),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::IoError(ref err) => Some(err),
+            Error::VendorListError(ref err) => Some(err),
+            Error::Other(_) => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IoError(ref err) => Display::fmt(err, f),
+            Error::VendorListError(ref err) => Display::fmt(err, f),
+            Error::Other(msg) => Display::fmt(msg, f),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::IoError(e)
+    }
+}
+
+impl From<vendor_list::Error> for Error {
+    fn from(e: vendor_list::Error) -> Error {
+        Error::VendorListError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VENDOR_LIST: &str = r#"{
+        "gvlSpecificationVersion": 2,
+        "vendorListVersion": 48,
+        "tcfPolicyVersion": 2,
+        "lastUpdated": "2020-07-02T16:00:23.5Z",
+        "purposes": {
+            "1": { "id": 1, "name": "Store and/or access information on a device", "description": "Cookies, device identifiers...", "descriptionLegal": "Vendors can: ..." }
+        },
+        "specialPurposes": {
+            "1": { "id": 1, "name": "Ensure security, prevent fraud, and debug", "description": "Your data can be used..." }
+        },
+        "features": {},
+        "specialFeatures": {
+            "1": { "id": 1, "name": "Use precise geolocation data", "description": "Your precise geolocation..." }
+        },
+        "stacks": {
+            "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Precise geolocation", "description": "Precise geolocation data..." }
+        },
+        "vendors": {
+            "8": {
+                "id": 8, "name": "Emerse Sverige AB", "purposes": [1], "legIntPurposes": [], "flexiblePurposes": [],
+                "specialPurposes": [1], "features": [], "specialFeatures": [1],
+                "policyUrl": "https://www.emerse.com/privacy-policy/", "usesNonCookieAccess": false,
+                "cookieMaxAgeSeconds": 31536000
+            },
+            "32": {
+                "id": 32, "name": "Xandr, Inc.", "purposes": [1], "legIntPurposes": [], "flexiblePurposes": [],
+                "specialPurposes": [], "features": [], "specialFeatures": [],
+                "policyUrl": "https://www.xandr.com/privacy/platform-privacy-policy/",
+                "deletedDate": "2020-06-11T00:00:00Z", "overflow": { "httpGetLimit": 32 }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn round_trip() {
+        let snapshot = from_json(VENDOR_LIST, &StripOptions::default()).unwrap();
+        assert_eq!(strip_options(&snapshot).unwrap(), StripOptions::default());
+
+        let vendor_list = decode(&snapshot).unwrap();
+        let original: VendorList = VENDOR_LIST.parse().unwrap();
+        assert_eq!(
+            vendor_list.to_string().unwrap(),
+            original.to_string().unwrap()
+        );
+        let geo = vendor_list.vendors_with_special_feature(1);
+        assert_eq!(geo.len(), 1);
+        assert_eq!(geo[0].id, VendorId(8));
+        assert_eq!(
+            encode(&vendor_list, &StripOptions::default()).unwrap(),
+            snapshot
+        );
+
+        let path = std::env::temp_dir().join(format!("gdpr_consent-{}.gvls", std::process::id()));
+        fs::write(&path, &snapshot).unwrap();
+        let loaded = load(&path).unwrap();
+        // safety: the file is only written before it is mapped
+        let mapped = unsafe { load_mmap(&path).unwrap() };
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.to_string().unwrap(), original.to_string().unwrap());
+        assert_eq!(mapped.to_string().unwrap(), original.to_string().unwrap());
+    }
+
+    #[test]
+    fn round_trip_translations() {
+        let mut original: VendorList = VENDOR_LIST.parse().unwrap();
+        let translations = r#"{
+            "purposes": {
+                "1": { "id": 1, "name": "Stocker et/ou accéder à des informations sur un appareil", "description": "Cookies" }
+            },
+            "specialPurposes": {
+                "1": { "id": 1, "name": "Assurer la sécurité", "description": "Sécurité" }
+            },
+            "specialFeatures": {
+                "1": { "id": 1, "name": "Utiliser des données de géolocalisation précises", "description": "Géolocalisation" }
+            },
+            "stacks": {
+                "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Géolocalisation précise", "description": "Géolocalisation" }
+            }
+        }"#.parse().unwrap();
+        original.add_translations("fr", translations).unwrap();
+
+        let snapshot = encode(&original, &StripOptions::default()).unwrap();
+        let vendor_list = decode(&snapshot).unwrap();
+        assert_eq!(
+            vendor_list.purpose_name(1, "fr"),
+            Some("Stocker et/ou accéder à des informations sur un appareil")
+        );
+        assert_eq!(
+            vendor_list.special_purpose_name(1, "fr"),
+            Some("Assurer la sécurité")
+        );
+        assert_eq!(
+            vendor_list.special_feature_description(1, "fr"),
+            Some("Géolocalisation")
+        );
+        assert_eq!(
+            vendor_list.stack_name(1, "fr"),
+            Some("Géolocalisation précise")
+        );
+        assert_eq!(
+            vendor_list.stacks[&1].special_feature_ids,
+            vec![FeatureId(1)]
+        );
+        assert_eq!(
+            encode(&vendor_list, &StripOptions::default()).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn strip() {
+        let full = from_json(VENDOR_LIST, &StripOptions::default()).unwrap();
+        let stripped = from_json(VENDOR_LIST, &StripOptions::all()).unwrap();
+        assert!(stripped.len() < full.len());
+        assert_eq!(strip_options(&stripped).unwrap(), StripOptions::all());
+
+        let vendor_list = decode(&stripped).unwrap();
+        let vendor = &vendor_list.vendors[&8];
+        assert_eq!(vendor.name, "Emerse Sverige AB");
+        assert_eq!(vendor.policy_url, "");
+        assert_eq!(vendor.uses_non_cookie_access, Some(false));
+        assert!(vendor.extra.is_empty());
+        assert_eq!(vendor_list.purposes[&1].description, "");
+        assert_eq!(vendor_list.stacks[&1].description, "");
+        assert_eq!(
+            vendor_list.vendors[&32].overflow,
+            Some(Overflow { http_get_limit: 32 })
+        );
+    }
+
+    #[test]
+    fn decode_bad() {
+        let snapshot = from_json(VENDOR_LIST, &StripOptions::default()).unwrap();
+        assert!(decode(b"{}").is_err());
+        assert!(decode(&snapshot[..snapshot.len() - 1]).is_err());
+
+        let mut trailing = snapshot.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+
+        let mut future = snapshot;
+        future[4] = FORMAT_VERSION + 1;
+        assert!(decode(&future).is_err());
+    }
+}
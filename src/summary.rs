@@ -0,0 +1,303 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::Write;
+
+use ids::{PurposeId, VendorId};
+use timestamp::Deciseconds;
+use vendor_consent::VendorConsent;
+use vendor_list::{Vendor, VendorList};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PurposeSummary {
+    pub id: PurposeId,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct VendorSummary {
+    pub id: VendorId,
+    pub name: String,
+    #[serde(rename = "policyUrl")]
+    pub policy_url: String,
+}
+
+impl<'a> From<&'a Vendor> for VendorSummary {
+    fn from(vendor: &'a Vendor) -> VendorSummary {
+        VendorSummary {
+            id: vendor.id,
+            name: vendor.name.clone(),
+            policy_url: vendor.policy_url.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Summary {
+    // Language purpose names were looked up in
+    pub language: String,
+
+    #[serde(rename = "allowedPurposes")]
+    pub allowed_purposes: Vec<PurposeSummary>,
+
+    #[serde(rename = "consentedVendors")]
+    pub consented_vendors: Vec<VendorSummary>,
+
+    // Vendors in the vendor list that have not been given consent
+    #[serde(rename = "missingVendors")]
+    pub missing_vendors: Vec<VendorSummary>,
+}
+
+// Builds a report of what the consent string allows, naming purposes in the
+// given language (falling back to English) and vendors from the vendor list
+// that had not been deleted from it by `now`.
+pub fn summarize(
+    consent: &VendorConsent,
+    vendor_list: &VendorList,
+    now: Deciseconds,
+    language: &str,
+) -> Summary {
+    let mut purpose_ids: Vec<PurposeId> = vendor_list.purposes.keys().cloned().collect();
+    purpose_ids.sort();
+
+    let allowed_purposes = purpose_ids
+        .into_iter()
+        .filter(|id| consent.has_purpose_consent(*id))
+        .map(|id| PurposeSummary {
+            id,
+            name: vendor_list
+                .purpose_name(id, language)
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect();
+
+    // deleted vendors are listed as neither consented nor missing consent
+    let vendors = vendor_list.active_vendors_at(now);
+
+    let (consented, missing): (Vec<&Vendor>, Vec<&Vendor>) = vendors
+        .into_iter()
+        .partition(|v| consent.has_vendor_consent(v.id));
+
+    Summary {
+        language: language.to_string(),
+        allowed_purposes,
+        consented_vendors: consented.into_iter().map(From::from).collect(),
+        missing_vendors: missing.into_iter().map(From::from).collect(),
+    }
+}
+
+impl Summary {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("Allowed purposes:\n");
+        if self.allowed_purposes.is_empty() {
+            out.push_str("  (none)\n");
+        }
+        for p in &self.allowed_purposes {
+            let _ = writeln!(out, "  {}. {}", p.id, p.name);
+        }
+
+        for (title, vendors) in &[
+            ("Consented vendors", &self.consented_vendors),
+            ("Vendors missing consent", &self.missing_vendors),
+        ] {
+            let _ = writeln!(out, "{}:", title);
+            if vendors.is_empty() {
+                out.push_str("  (none)\n");
+            }
+            for v in vendors.iter() {
+                let _ = writeln!(out, "  {}. {} <{}>", v.id, v.name, v.policy_url);
+            }
+        }
+
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+
+        let _ = write!(
+            out,
+            "<section class=\"consent-summary\" lang=\"{}\">",
+            escape(&self.language)
+        );
+
+        out.push_str("<h2>Allowed purposes</h2><ul>");
+        for p in &self.allowed_purposes {
+            let _ = write!(
+                out,
+                "<li data-purpose-id=\"{}\">{}</li>",
+                p.id,
+                escape(&p.name)
+            );
+        }
+        out.push_str("</ul>");
+
+        for (title, vendors) in &[
+            ("Consented vendors", &self.consented_vendors),
+            ("Vendors missing consent", &self.missing_vendors),
+        ] {
+            let _ = write!(out, "<h2>{}</h2><ul>", title);
+            for v in vendors.iter() {
+                // vendor lists are untrusted input: only web URLs are linked,
+                // so a policy URL cannot run script when clicked
+                if is_web_url(&v.policy_url) {
+                    let _ = write!(
+                        out,
+                        "<li data-vendor-id=\"{}\"><a href=\"{}\">{}</a></li>",
+                        v.id,
+                        escape(&v.policy_url),
+                        escape(&v.name)
+                    );
+                } else if v.policy_url.is_empty() {
+                    let _ = write!(
+                        out,
+                        "<li data-vendor-id=\"{}\">{}</li>",
+                        v.id,
+                        escape(&v.name)
+                    );
+                } else {
+                    let _ = write!(
+                        out,
+                        "<li data-vendor-id=\"{}\">{} &lt;{}&gt;</li>",
+                        v.id,
+                        escape(&v.name),
+                        escape(&v.policy_url)
+                    );
+                }
+            }
+            out.push_str("</ul>");
+        }
+
+        out.push_str("</section>");
+        out
+    }
+}
+
+// Whether the URL has an http or https scheme
+fn is_web_url(url: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| {
+        url.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_fixtures::{now, v1_vendor_list, V1_CONSENT};
+
+    #[test]
+    fn summarize_good() {
+        // vendor 9 refused, all others consented; purposes 1-3 allowed
+        let consent = V1_CONSENT.parse().unwrap();
+        let summary = summarize(&consent, &v1_vendor_list(), now(), "en");
+
+        assert_eq!(
+            summary.allowed_purposes,
+            vec![
+                PurposeSummary {
+                    id: PurposeId(1),
+                    name: "Information storage and access".to_string()
+                },
+                PurposeSummary {
+                    id: PurposeId(2),
+                    name: "Personalisation".to_string()
+                },
+            ]
+        );
+        assert_eq!(summary.consented_vendors.len(), 2);
+        assert_eq!(summary.consented_vendors[0].id, VendorId(1));
+        assert_eq!(summary.missing_vendors.len(), 1);
+        assert_eq!(summary.missing_vendors[0].id, VendorId(9));
+
+        assert_eq!(
+            summary.to_text(),
+            "Allowed purposes:\n  1. Information storage and access\n  2. Personalisation\n\
+             Consented vendors:\n  1. Example Vendor <https://example.com/privacy>\n  2. Vendor 2 <https://example.net/privacy>\n\
+             Vendors missing consent:\n  9. Ads & <Co> <https://example.org/privacy>\n"
+        );
+        assert!(summary
+            .to_html()
+            .contains("<li data-vendor-id=\"9\"><a href=\"https://example.org/privacy\">Ads &amp; &lt;Co&gt;</a></li>"));
+    }
+
+    #[test]
+    fn summarize_deleted_vendors() {
+        let consent = V1_CONSENT.parse().unwrap();
+        let mut vendor_list = v1_vendor_list();
+        vendor_list.vendors.get_mut(&9).unwrap().deleted_date =
+            Some("2020-01-01T00:00:00Z".parse().unwrap());
+
+        let before = summarize(
+            &consent,
+            &vendor_list,
+            "2019-12-31T00:00:00Z".parse().unwrap(),
+            "en",
+        );
+        assert_eq!(before.missing_vendors.len(), 1);
+
+        let after = summarize(&consent, &vendor_list, now(), "en");
+        assert_eq!(after.consented_vendors.len(), 2);
+        assert!(after.missing_vendors.is_empty());
+    }
+
+    #[test]
+    fn to_html_links_only_web_urls() {
+        let vendor = |id, policy_url: &str| VendorSummary {
+            id: VendorId(id),
+            name: format!("Vendor {}", id),
+            policy_url: policy_url.to_string(),
+        };
+        let summary = Summary {
+            language: "en".to_string(),
+            allowed_purposes: vec![],
+            consented_vendors: vec![
+                vendor(1, "HTTPS://example.com/privacy"),
+                vendor(2, "http://example.net/privacy"),
+            ],
+            missing_vendors: vec![
+                vendor(3, "javascript:alert(1)"),
+                vendor(4, " https://example.org/privacy"),
+                vendor(5, ""),
+            ],
+        };
+
+        let html = summary.to_html();
+        assert!(html.contains(
+            "<li data-vendor-id=\"1\"><a href=\"HTTPS://example.com/privacy\">Vendor 1</a></li>"
+        ));
+        assert!(html.contains(
+            "<li data-vendor-id=\"2\"><a href=\"http://example.net/privacy\">Vendor 2</a></li>"
+        ));
+        assert!(html.contains("<li data-vendor-id=\"3\">Vendor 3 &lt;javascript:alert(1)&gt;</li>"));
+        assert!(html.contains(
+            "<li data-vendor-id=\"4\">Vendor 4 &lt; https://example.org/privacy&gt;</li>"
+        ));
+        assert!(html.contains("<li data-vendor-id=\"5\">Vendor 5</li>"));
+        assert_eq!(html.matches("href").count(), 2);
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Consent strings and vendor lists shared by the unit tests.
+
+use timestamp::Deciseconds;
+use vendor_list::VendorList;
+
+// Vendor list version 8, last updated 2017-11-07; purposes 1-3 allowed;
+// vendor 9 refused, all others up to 2011 consented
+pub const V1_CONSENT: &str = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+
+// Vendor list version 15, CMP 27, last updated 2020-02-21; purposes 1-3
+// consented; vendors 2, 6 and 8 consented and under legitimate interest
+pub const V2_CONSENT: &str = "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA";
+
+// Time the tests evaluate consent at, after vendor 8's deletion from the v2
+// vendor list
+pub fn now() -> Deciseconds {
+    "2020-07-01T00:00:00Z".parse().unwrap()
+}
+
+pub fn v1_vendor_list() -> VendorList {
+    r#"{
+        "vendorListVersion": 8,
+        "lastUpdated": "2018-05-28T16:00:15Z",
+        "purposes": [
+            { "id": 1, "name": "Information storage and access", "description": "" },
+            { "id": 2, "name": "Personalisation", "description": "" },
+            { "id": 4, "name": "Content selection, delivery, reporting", "description": "" }
+        ],
+        "features": [],
+        "vendors": [
+            { "id": 1, "name": "Example Vendor", "policyUrl": "https://example.com/privacy", "purposeIds": [1, 2], "legIntPurposeIds": [], "featureIds": [] },
+            { "id": 2, "name": "Vendor 2", "policyUrl": "https://example.net/privacy", "purposeIds": [2], "legIntPurposeIds": [], "featureIds": [] },
+            { "id": 9, "name": "Ads & <Co>", "policyUrl": "https://example.org/privacy", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] }
+        ]
+    }"#
+    .parse()
+    .unwrap()
+}
+
+pub fn v2_vendor_list() -> VendorList {
+    r#"{
+        "gvlSpecificationVersion": 2,
+        "vendorListVersion": 15,
+        "tcfPolicyVersion": 2,
+        "lastUpdated": "2020-01-01T00:00:00Z",
+        "purposes": {
+            "1": { "id": 1, "name": "Store and/or access information on a device", "description": "", "descriptionLegal": "" },
+            "2": { "id": 2, "name": "Select basic ads", "description": "", "descriptionLegal": "" },
+            "3": { "id": 3, "name": "Create a personalised ads profile", "description": "", "descriptionLegal": "" }
+        },
+        "specialPurposes": {},
+        "features": {},
+        "specialFeatures": {
+            "1": { "id": 1, "name": "Use precise geolocation data", "description": "", "descriptionLegal": "" }
+        },
+        "stacks": {},
+        "vendors": {
+            "1": { "id": 1, "name": "Vendor 1", "purposes": [1], "legIntPurposes": [2, 7], "flexiblePurposes": [], "specialPurposes": [], "features": [], "specialFeatures": [1], "policyUrl": "" },
+            "2": { "id": 2, "name": "Vendor 2", "purposes": [1, 2], "legIntPurposes": [7], "flexiblePurposes": [], "specialPurposes": [], "features": [], "specialFeatures": [], "policyUrl": "" },
+            "3": { "id": 3, "name": "Vendor 3", "purposes": [1], "legIntPurposes": [], "flexiblePurposes": [], "specialPurposes": [], "features": [], "specialFeatures": [], "policyUrl": "" },
+            "6": { "id": 6, "name": "Vendor 6", "purposes": [2], "legIntPurposes": [], "flexiblePurposes": [], "specialPurposes": [], "features": [], "specialFeatures": [], "policyUrl": "" },
+            "8": { "id": 8, "name": "Vendor 8", "purposes": [1], "legIntPurposes": [], "flexiblePurposes": [], "specialPurposes": [], "features": [], "specialFeatures": [], "policyUrl": "", "deletedDate": "2020-06-01T00:00:00Z" }
+        }
+    }"#
+    .parse()
+    .unwrap()
+}
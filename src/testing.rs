@@ -0,0 +1,479 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Consent strings for tests: proptest strategies (and Arbitrary impls) for
+// consent, a Generator drawing from them outside of proptest, and named
+// fixtures for edge cases. Everything handed out as a (VendorConsent, String)
+// pair has been through the real encoder and decodes back to the same value.
+
+use std::fmt::Debug;
+
+use base64;
+use proptest::collection;
+use proptest::prelude::*;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+
+use ids::{FeatureSet, Id, IdSet, PurposeId, PurposeSet, VendorId, VendorSet};
+use timestamp::Deciseconds;
+use vendor_consent::{Encoding, PublisherRestriction, RestrictionType, VendorConsent, V1, V2};
+
+// Encodes the consent, panicking unless the string decodes back to it.
+pub fn encode(consent: VendorConsent) -> (VendorConsent, String) {
+    let s = consent
+        .to_string()
+        .unwrap_or_else(|e| panic!("Failed to encode {:?}: {}", consent, e));
+    decode(consent, s)
+}
+
+fn decode(consent: VendorConsent, s: String) -> (VendorConsent, String) {
+    let decoded: VendorConsent = s
+        .parse()
+        .unwrap_or_else(|e| panic!("Failed to decode {}: {}", s, e));
+    assert_eq!(decoded, consent, "{} does not round-trip", s);
+    (consent, s)
+}
+
+// Vendor sets shaped like real consent: a few vendors, all but a few, or
+// runs of consecutive IDs. Empty when max_vendor_id is 0.
+pub fn vendor_set(max_vendor_id: u16) -> BoxedStrategy<VendorSet> {
+    if max_vendor_id == 0 {
+        return Just(VendorSet::new()).boxed();
+    }
+
+    let ids = 1..=max_vendor_id;
+    let sparse = collection::vec(ids.clone(), 0..=32)
+        .prop_map(|ids| ids.into_iter().map(VendorId).collect::<VendorSet>());
+    let dense = collection::vec(ids.clone(), 0..=32).prop_map(move |excluded| {
+        (1..=max_vendor_id)
+            .filter(|id| !excluded.contains(id))
+            .map(VendorId)
+            .collect()
+    });
+    let runs = collection::vec((ids, 1..=64u16), 0..=16).prop_map(move |runs| {
+        runs.into_iter()
+            .flat_map(|(start, len)| start..=start.saturating_add(len - 1).min(max_vendor_id))
+            .map(VendorId)
+            .collect()
+    });
+
+    prop_oneof![sparse, dense, runs].boxed()
+}
+
+// Any subset of the first len IDs, as stored in fixed-length bitfields
+fn id_set<T: Id + Debug>(len: usize) -> impl Strategy<Value = IdSet<T>> {
+    collection::vec(any::<bool>(), len).prop_map(|bits| {
+        bits.into_iter()
+            .enumerate()
+            .filter(|&(_, set)| set)
+            .filter_map(|(i, _)| T::from_index(i))
+            .collect()
+    })
+}
+
+fn letters(base: u8) -> impl Strategy<Value = String> {
+    (base..base + 26, base..base + 26).prop_map(|(a, b)| String::from_utf8(vec![a, b]).unwrap())
+}
+
+// Created between the GDPR taking effect and 2030, last updated up to a year
+// later.
+fn timestamps() -> impl Strategy<Value = (Deciseconds, Deciseconds)> {
+    (15_272_064_000u64..18_934_560_000, 0u64..315_360_000).prop_map(|(created, age)| {
+        (
+            Deciseconds::new(created).unwrap(),
+            Deciseconds::new(created + age).unwrap(),
+        )
+    })
+}
+
+// cmp_id, cmp_version, consent_screen, vendor_list_version
+fn header() -> impl Strategy<Value = (u16, u16, u8, u16)> {
+    (1u16..4096, 0u16..4096, 0u8..64, 1u16..4096)
+}
+
+// Vendors up to the size of current vendor lists
+fn vendors() -> impl Strategy<Value = (usize, VendorSet)> {
+    (0u16..=1500)
+        .prop_flat_map(vendor_set)
+        .prop_map(|vendors| (vendors.max().map_or(0, |id| id.0 as usize), vendors))
+}
+
+// Opaque segment following the core segment of a TCF v2 string
+fn segment() -> impl Strategy<Value = String> {
+    collection::vec(any::<u8>(), 1..=32)
+        .prop_map(|bytes| base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD))
+}
+
+fn publisher_restriction() -> impl Strategy<Value = PublisherRestriction> {
+    let restriction_type = prop_oneof![
+        Just(RestrictionType::NotAllowed),
+        Just(RestrictionType::RequireConsent),
+        Just(RestrictionType::RequireLegitimateInterest),
+    ];
+    (
+        1u8..=10,
+        restriction_type,
+        (1u16..=1500).prop_flat_map(vendor_set),
+    )
+        .prop_map(
+            |(purpose_id, restriction_type, vendors)| PublisherRestriction {
+                purpose_id: PurposeId(purpose_id),
+                restriction_type,
+                vendors,
+            },
+        )
+}
+
+pub fn v1() -> BoxedStrategy<V1> {
+    (timestamps(), header(), letters(b'a'), id_set(24), vendors())
+        .prop_map(
+            |(
+                (created, last_updated),
+                (cmp_id, cmp_version, consent_screen, vendor_list_version),
+                consent_language,
+                purposes_allowed,
+                (max_vendor_id, vendor_consent),
+            )| V1 {
+                created,
+                last_updated,
+                cmp_id,
+                cmp_version,
+                consent_screen,
+                consent_language,
+                vendor_list_version,
+                purposes_allowed,
+                max_vendor_id,
+                vendor_consent,
+            },
+        )
+        .boxed()
+}
+
+pub fn v2() -> BoxedStrategy<V2> {
+    let purposes = (
+        id_set(12),
+        id_set(24),
+        id_set(24),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+    );
+    let vendors = (
+        vendors(),
+        vendors(),
+        collection::vec(publisher_restriction(), 0..=3),
+        collection::vec(segment(), 0..=2),
+    );
+
+    (
+        timestamps(),
+        header(),
+        letters(b'A'),
+        letters(b'A'),
+        purposes,
+        vendors,
+    )
+        .prop_map(
+            |(
+                (created, last_updated),
+                (cmp_id, cmp_version, consent_screen, vendor_list_version),
+                consent_language,
+                publisher_cc,
+                (
+                    special_feature_opt_ins,
+                    purposes_consent,
+                    purposes_li_transparency,
+                    is_service_specific,
+                    use_non_standard_stacks,
+                    purpose_one_treatment,
+                ),
+                (
+                    (max_vendor_id, vendor_consent),
+                    (max_vendor_legitimate_interest_id, vendor_legitimate_interest),
+                    publisher_restrictions,
+                    segments,
+                ),
+            )| V2 {
+                created,
+                last_updated,
+                cmp_id,
+                cmp_version,
+                consent_screen,
+                consent_language,
+                vendor_list_version,
+                tcf_policy_version: 2,
+                is_service_specific,
+                use_non_standard_stacks,
+                special_feature_opt_ins,
+                purposes_consent,
+                purposes_li_transparency,
+                purpose_one_treatment,
+                publisher_cc,
+                max_vendor_id,
+                vendor_consent,
+                max_vendor_legitimate_interest_id,
+                vendor_legitimate_interest,
+                publisher_restrictions,
+                segments,
+            },
+        )
+        .boxed()
+}
+
+pub fn vendor_consent() -> BoxedStrategy<VendorConsent> {
+    prop_oneof![
+        v1().prop_map(VendorConsent::V1),
+        v2().prop_map(VendorConsent::V2)
+    ]
+    .boxed()
+}
+
+impl Arbitrary for V1 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<V1>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<V1> {
+        v1()
+    }
+}
+
+impl Arbitrary for V2 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<V2>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<V2> {
+        v2()
+    }
+}
+
+impl Arbitrary for VendorConsent {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<VendorConsent>;
+
+    fn arbitrary_with(_: ()) -> BoxedStrategy<VendorConsent> {
+        vendor_consent()
+    }
+}
+
+// Draws encoded consent strings from the strategies above. Unless seeded, a
+// generator yields the same sequence on every run.
+pub struct Generator {
+    runner: TestRunner,
+}
+
+impl Generator {
+    pub fn new() -> Generator {
+        Generator {
+            runner: TestRunner::deterministic(),
+        }
+    }
+
+    pub fn from_seed(seed: [u8; 32]) -> Generator {
+        let rng = TestRng::from_seed(RngAlgorithm::ChaCha, &seed);
+        Generator {
+            runner: TestRunner::new_with_rng(Config::default(), rng),
+        }
+    }
+
+    pub fn generate<S>(&mut self, strategy: &S) -> (VendorConsent, String)
+    where
+        S: Strategy<Value = VendorConsent>,
+    {
+        let tree = strategy
+            .new_tree(&mut self.runner)
+            .unwrap_or_else(|e| panic!("Failed to generate a consent string: {}", e));
+        encode(tree.current())
+    }
+
+    pub fn v1(&mut self) -> (VendorConsent, String) {
+        self.generate(&v1().prop_map(VendorConsent::V1))
+    }
+
+    pub fn v2(&mut self) -> (VendorConsent, String) {
+        self.generate(&v2().prop_map(VendorConsent::V2))
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator::new()
+    }
+}
+
+// Endless v1 and v2 consent strings
+impl Iterator for Generator {
+    type Item = (VendorConsent, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.generate(&vendor_consent()))
+    }
+}
+
+// Base of the v2 fixtures: no purposes, features or vendors
+fn fixture_v2() -> V2 {
+    V2 {
+        created: Deciseconds::new(15_900_000_000).unwrap(),
+        last_updated: Deciseconds::new(15_900_000_000).unwrap(),
+        cmp_id: 10,
+        cmp_version: 1,
+        consent_screen: 1,
+        consent_language: "EN".to_string(),
+        vendor_list_version: 50,
+        tcf_policy_version: 2,
+        is_service_specific: false,
+        use_non_standard_stacks: false,
+        special_feature_opt_ins: FeatureSet::new(),
+        purposes_consent: PurposeSet::new(),
+        purposes_li_transparency: PurposeSet::new(),
+        purpose_one_treatment: false,
+        publisher_cc: "AA".to_string(),
+        max_vendor_id: 0,
+        vendor_consent: VendorSet::new(),
+        max_vendor_legitimate_interest_id: 0,
+        vendor_legitimate_interest: VendorSet::new(),
+        publisher_restrictions: Vec::new(),
+        segments: Vec::new(),
+    }
+}
+
+fn fixture_v1() -> V1 {
+    V1 {
+        created: Deciseconds::new(15_270_000_000).unwrap(),
+        last_updated: Deciseconds::new(15_270_000_000).unwrap(),
+        cmp_id: 10,
+        cmp_version: 1,
+        consent_screen: 1,
+        consent_language: "en".to_string(),
+        vendor_list_version: 8,
+        purposes_allowed: PurposeSet::new(),
+        max_vendor_id: 0,
+        vendor_consent: VendorSet::new(),
+    }
+}
+
+// Consent to nothing
+pub fn empty() -> (VendorConsent, String) {
+    encode(VendorConsent::V2(fixture_v2()))
+}
+
+pub fn empty_v1() -> (VendorConsent, String) {
+    encode(VendorConsent::V1(fixture_v1()))
+}
+
+// Consent to purposes 1-10 and every vendor up to max_vendor_id
+pub fn all_vendors(max_vendor_id: u16) -> (VendorConsent, String) {
+    let vendors: VendorSet = (1..=max_vendor_id).map(VendorId).collect();
+    encode(VendorConsent::V2(V2 {
+        purposes_consent: (1..=10).map(PurposeId).collect(),
+        max_vendor_id: max_vendor_id as usize,
+        vendor_consent: vendors.clone(),
+        max_vendor_legitimate_interest_id: max_vendor_id as usize,
+        vendor_legitimate_interest: vendors,
+        ..fixture_v2()
+    }))
+}
+
+// Consent to purposes 1-5 and every vendor up to max_vendor_id, range encoded
+// with all vendors consented by default
+pub fn all_vendors_v1(max_vendor_id: u16) -> (VendorConsent, String) {
+    encode(VendorConsent::V1(V1 {
+        purposes_allowed: (1..=5).map(PurposeId).collect(),
+        max_vendor_id: max_vendor_id as usize,
+        vendor_consent: (1..=max_vendor_id).map(VendorId).collect(),
+        ..fixture_v1()
+    }))
+}
+
+// Consent to purpose 1 and a single vendor
+pub fn single_vendor(vendor_id: VendorId) -> (VendorConsent, String) {
+    let vendors: VendorSet = Some(vendor_id).into_iter().collect();
+    encode(VendorConsent::V2(V2 {
+        purposes_consent: Some(PurposeId(1)).into_iter().collect(),
+        max_vendor_id: vendor_id.0 as usize,
+        vendor_consent: vendors,
+        ..fixture_v2()
+    }))
+}
+
+// A single vendor at the highest ID a consent string can hold
+pub fn max_vendor_id() -> (VendorConsent, String) {
+    single_vendor(VendorId(u16::MAX))
+}
+
+// A v1 vendor consent section range-encoded with 4095 single vendor entries,
+// as many as the 12-bit count allows: vendors 1, 3, ..., 8189 have consent by
+// default and the entries list vendors 2, 4, ..., 8190 without it. A bitfield
+// would be shorter, so the encoder is told to use a range.
+pub fn max_range_entries() -> (VendorConsent, String) {
+    let v1 = V1 {
+        purposes_allowed: Some(PurposeId(1)).into_iter().collect(),
+        max_vendor_id: 8190,
+        vendor_consent: (0..4095).map(|i| VendorId(i * 2 + 1)).collect(),
+        ..fixture_v1()
+    };
+    let s = v1
+        .to_string_with(Encoding::Range)
+        .unwrap_or_else(|e| panic!("Failed to encode {:?}: {}", v1, e));
+    decode(VendorConsent::V1(v1), s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures() {
+        let (consent, _) = empty();
+        assert_eq!(consent.max_vendor_id(), 0);
+        assert!(!consent.has_purpose_consent(1));
+        empty_v1();
+
+        let (consent, _) = all_vendors(1000);
+        assert!(consent.has_vendor_consent(1) && consent.has_vendor_consent(1000));
+        let (consent, _) = all_vendors_v1(1000);
+        assert!(consent.has_vendor_consent(1) && consent.has_vendor_consent(1000));
+
+        // a single range entry rather than a 65535 bit bitfield
+        let (consent, s) = max_vendor_id();
+        assert!(consent.has_vendor_consent(65535));
+        assert!(!consent.has_vendor_consent(65534));
+        assert!(s.len() < 100);
+
+        let (consent, s) = max_range_entries();
+        match consent {
+            VendorConsent::V1(ref v1) => {
+                assert_eq!(v1.max_vendor_id, 8190);
+                assert_eq!(v1.vendor_consent.len(), 4095);
+                assert_eq!(v1.vendor_consent.max(), Some(VendorId(8189)));
+            }
+            ref other => panic!("expected a v1 consent string, got {:?}", other),
+        }
+        // 4095 entries of 17 bits, where the encoder's bitfield is 8190 bits
+        assert!(s.len() > 4095 * 17 / 6);
+        assert!(consent.to_string().unwrap().len() < 8190 / 6 + 100);
+    }
+
+    #[test]
+    fn generator() {
+        let first: Vec<String> = Generator::new().take(10).map(|(_, s)| s).collect();
+        let again: Vec<String> = Generator::new().take(10).map(|(_, s)| s).collect();
+        assert_eq!(first, again);
+
+        let mut generator = Generator::from_seed([7; 32]);
+        assert!(matches!(generator.v1(), (VendorConsent::V1(_), _)));
+        assert!(matches!(generator.v2(), (VendorConsent::V2(_), _)));
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(consent in any::<VendorConsent>()) {
+            let s = consent.to_string().unwrap();
+            prop_assert_eq!(s.parse::<VendorConsent>().unwrap(), consent);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright (c) 2018 The gdpr_consent authors
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+use chrono::{DateTime, TimeZone, Timelike, Utc};
+#[cfg(feature = "std")]
+use serde;
+
+use vendor_consent::Error;
+
+const DECISECS_IN_SEC: u64 = 10;
+const NANOSECS_IN_DECISEC: u32 = 100_000_000;
+
+// Consent string timestamps: deciseconds since the Unix epoch, stored in 36 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deciseconds(u64);
+
+impl Deciseconds {
+    // Latest timestamp a consent string can hold (around the year 2187)
+    pub const MAX: Deciseconds = Deciseconds((1 << 36) - 1);
+
+    pub fn new(deciseconds: u64) -> Result<Deciseconds, Error> {
+        if deciseconds > Deciseconds::MAX.0 {
+            return Err(Error::Other(format!(
+                "Timestamp {} does not fit in 36 bits of deciseconds",
+                deciseconds
+            )));
+        }
+        Ok(Deciseconds(deciseconds))
+    }
+
+    // Fails if the timestamp is out of range or more precise than a decisecond.
+    pub fn from_datetime(t: &DateTime<Utc>) -> Result<Deciseconds, Error> {
+        if !t.nanosecond().is_multiple_of(NANOSECS_IN_DECISEC) {
+            return Err(Error::Other(format!(
+                "Timestamp {} is more precise than a decisecond",
+                t.to_rfc3339()
+            )));
+        }
+        Deciseconds::from_datetime_truncated(t)
+    }
+
+    // Fails if the timestamp is out of range, dropping precision below a decisecond.
+    pub fn from_datetime_truncated(t: &DateTime<Utc>) -> Result<Deciseconds, Error> {
+        if t.timestamp() < 0 {
+            return Err(Error::Other(format!(
+                "Timestamp {} is before the Unix epoch",
+                t.to_rfc3339()
+            )));
+        }
+
+        // leap seconds are reported as nanoseconds past 1_000_000_000
+        let subsec = (t.nanosecond() % 1_000_000_000 / NANOSECS_IN_DECISEC) as u64;
+        (t.timestamp() as u64)
+            .checked_mul(DECISECS_IN_SEC)
+            .ok_or_else(|| Error::Other(format!("Timestamp {} is out of range", t.to_rfc3339())))
+            .and_then(|d| Deciseconds::new(d + subsec))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn now() -> Deciseconds {
+        Deciseconds::from_datetime_truncated(&Utc::now()).expect("system clock out of range")
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        // every 36 bit value is well within the range chrono supports
+        Utc.timestamp_opt(
+            (self.0 / DECISECS_IN_SEC) as i64,
+            (self.0 % DECISECS_IN_SEC) as u32 * NANOSECS_IN_DECISEC,
+        )
+        .unwrap()
+    }
+}
+
+impl From<Deciseconds> for DateTime<Utc> {
+    fn from(t: Deciseconds) -> DateTime<Utc> {
+        t.to_datetime()
+    }
+}
+
+// RFC 3339, e.g. "2017-11-07T19:15:55.4Z"
+impl FromStr for Deciseconds {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let t = DateTime::parse_from_rfc3339(s)
+            .map_err(|e| Error::Other(format!("Invalid timestamp '{}': {}", s, e)))?;
+        Deciseconds::from_datetime(&t.with_timezone(&Utc))
+    }
+}
+
+impl Display for Deciseconds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.to_datetime().to_rfc3339(), f)
+    }
+}
+
+// Serialized like chrono::DateTime<Utc>
+#[cfg(feature = "std")]
+impl serde::Serialize for Deciseconds {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_datetime().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datetime_round_trip() {
+        let t: Deciseconds = "2017-11-07T19:15:55.4Z".parse().unwrap();
+        assert_eq!(t.as_u64(), 15100821554);
+        assert_eq!(Deciseconds::from_datetime(&t.to_datetime()).unwrap(), t);
+
+        let max = Deciseconds::MAX.to_datetime();
+        assert_eq!(Deciseconds::from_datetime(&max).unwrap(), Deciseconds::MAX);
+        assert!(Deciseconds::new(Deciseconds::MAX.as_u64() + 1).is_err());
+        assert!(
+            Deciseconds::from_datetime_truncated(&(max + chrono::Duration::seconds(1))).is_err()
+        );
+    }
+
+    #[test]
+    fn datetime_precision() {
+        assert!("2017-11-07T19:15:55.45Z".parse::<Deciseconds>().is_err());
+        assert!("1969-12-31T23:59:59Z".parse::<Deciseconds>().is_err());
+
+        let t = "2017-11-07T19:15:55.45Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            Deciseconds::from_datetime_truncated(&t).unwrap().as_u64(),
+            15100821554
+        );
+    }
+}
@@ -6,26 +6,35 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::convert::From;
+use alloc::string::{self, String};
+use alloc::vec::Vec;
+use core::convert::From;
+use core::fmt::{self, Display};
+use core::str::FromStr;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt::{self, Display};
-use std::io;
-use std::str::FromStr;
-use std::string;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
 use base64;
 use bit_set::BitSet;
 use bit_vec::BitVec;
-use bitstream_io::{BigEndian, BitReader, BitWriter};
-use chrono::{DateTime, TimeZone, Utc};
+
+#[cfg(feature = "archive")]
+use archive::VendorListArchive;
+use bits::{BitReader, BitWriter};
+#[cfg(feature = "std")]
+use cmp_list::{CmpList, CmpStatus};
+use ids::{FeatureId, FeatureSet, PurposeId, PurposeSet, VendorId, VendorSet};
+use timestamp::Deciseconds;
 
 #[derive(Debug, PartialEq)]
 pub struct V1 {
-    // Epoch ms when consent string was first created
-    pub created: DateTime<Utc>,
+    // Epoch deciseconds when consent string was first created
+    pub created: Deciseconds,
 
-    // Epoch ms when consent string was last updated
-    pub last_updated: DateTime<Utc>,
+    // Epoch deciseconds when consent string was last updated
+    pub last_updated: Deciseconds,
 
     // Consent Manager Provider ID that last updated the consent string
     pub cmp_id: u16,
@@ -43,53 +52,413 @@ pub struct V1 {
     pub vendor_list_version: u16,
 
     // For each purpose listed in the global vendor list, the presence indicates consent.
-    pub purposes_allowed: BitSet,
+    pub purposes_allowed: PurposeSet,
 
-    // Maximum vendor ID represented in the vendor_consent BitSet.
+    // Maximum vendor ID represented in the vendor_consent set.
     pub max_vendor_id: usize,
 
     // For each vendor id listend in the global vendor list, the presence indicates consent.
-    // Vendor IDs are offset by 1 (e.g. bit 0 corresponds with vendor ID 1.
-    pub vendor_consent: BitSet,
+    pub vendor_consent: VendorSet,
+}
+
+// How the vendor consent section of a v1 consent string is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    // Whichever is shorter, as to_string() does
+    Shortest,
+    Bitfield,
+    Range,
+}
+
+impl V1 {
+    pub fn to_string(&self) -> Result<String, Error> {
+        serialize_v1(self, Encoding::Shortest)
+    }
+
+    // Encodes the vendor consent section as given, even where the other
+    // encoding is shorter. Fails if a range would need more than 4095 entries.
+    pub fn to_string_with(&self, encoding: Encoding) -> Result<String, Error> {
+        serialize_v1(self, encoding)
+    }
+
+    pub fn has_purpose_consent<P: Into<PurposeId>>(&self, purpose_id: P) -> bool {
+        self.purposes_allowed.contains(purpose_id.into())
+    }
+
+    pub fn has_vendor_consent<V: Into<VendorId>>(&self, vendor_id: V) -> bool {
+        let vendor_id = vendor_id.into();
+        vendor_id.0 as usize <= self.max_vendor_id && self.vendor_consent.contains(vendor_id)
+    }
+
+    // The consent string's header without the purposes and vendors, as the
+    // v1 CMP API returns it from getVendorConsents
+    pub fn metadata_string(&self) -> Result<String, Error> {
+        let mut writer = BitWriter::new();
+        writer.write(6, 1)?;
+        writer.write(36, self.created.as_u64())?;
+        writer.write(36, self.last_updated.as_u64())?;
+        writer.write(12, self.cmp_id as u64)?;
+        writer.write(12, self.cmp_version as u64)?;
+        writer.write(6, self.consent_screen as u64)?;
+        writer.write(12, self.vendor_list_version as u64)?;
+        Ok(base64::encode_config(
+            writer.into_bytes(),
+            base64::URL_SAFE_NO_PAD,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictionType {
+    // Purpose is flatly not allowed by the publisher
+    NotAllowed,
+    // Vendor must rely on consent for the purpose
+    RequireConsent,
+    // Vendor must rely on legitimate interest for the purpose
+    RequireLegitimateInterest,
+}
+
+impl RestrictionType {
+    // As encoded in consent strings and the CMP API
+    pub fn as_u8(self) -> u8 {
+        match self {
+            RestrictionType::NotAllowed => 0,
+            RestrictionType::RequireConsent => 1,
+            RestrictionType::RequireLegitimateInterest => 2,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PublisherRestriction {
+    pub purpose_id: PurposeId,
+
+    pub restriction_type: RestrictionType,
+
+    // Vendors the restriction applies to.
+    pub vendors: VendorSet,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct V2 {
+    // Epoch deciseconds when consent string was first created
+    pub created: Deciseconds,
+
+    // Epoch deciseconds when consent string was last updated
+    pub last_updated: Deciseconds,
+
+    // Consent Manager Provider ID that last updated the consent string
+    pub cmp_id: u16,
+
+    // Consent Manager Provider version
+    pub cmp_version: u16,
+
+    // Screen number in the CMP where consent was given
+    pub consent_screen: u8,
+
+    // Two-letter ISO639-1 language code that CMP asked for consent in (upper case)
+    pub consent_language: String,
+
+    // Version of vendor list used in most recent consent string update.
+    pub vendor_list_version: u16,
+
+    // Version of the TCF policy used in most recent consent string update.
+    pub tcf_policy_version: u8,
+
+    // Whether the signals apply only to the service that stored them
+    pub is_service_specific: bool,
+
+    // Whether the CMP used non-IAB standard stacks
+    pub use_non_standard_stacks: bool,
+
+    // For each special feature, the presence indicates the user opted in.
+    pub special_feature_opt_ins: FeatureSet,
+
+    // For each purpose, the presence indicates consent.
+    pub purposes_consent: PurposeSet,
+
+    // For each purpose, the presence indicates legitimate interest was established.
+    pub purposes_li_transparency: PurposeSet,
+
+    // Whether purpose 1 was disclosed to the user by the publisher rather than the CMP
+    pub purpose_one_treatment: bool,
+
+    // Two-letter ISO3166-1 alpha-2 country code of the publisher (upper case)
+    pub publisher_cc: String,
+
+    // Maximum vendor ID represented in the vendor_consent set.
+    pub max_vendor_id: usize,
+
+    // For each vendor id listed in the global vendor list, the presence indicates consent.
+    pub vendor_consent: VendorSet,
+
+    // Maximum vendor ID represented in the vendor_legitimate_interest set.
+    pub max_vendor_legitimate_interest_id: usize,
+
+    // For each vendor id, the presence indicates legitimate interest was established.
+    pub vendor_legitimate_interest: VendorSet,
+
+    pub publisher_restrictions: Vec<PublisherRestriction>,
+
+    // Segments following the core segment (disclosed vendors, allowed vendors,
+    // publisher TC), kept undecoded in web-safe base64 and written back as is.
+    pub segments: Vec<String>,
 }
 
+impl V2 {
+    pub fn to_string(&self) -> Result<String, Error> {
+        serialize_v2(self)
+    }
+
+    pub fn has_purpose_consent<P: Into<PurposeId>>(&self, purpose_id: P) -> bool {
+        self.purposes_consent.contains(purpose_id.into())
+    }
+
+    pub fn has_vendor_consent<V: Into<VendorId>>(&self, vendor_id: V) -> bool {
+        let vendor_id = vendor_id.into();
+        vendor_id.0 as usize <= self.max_vendor_id && self.vendor_consent.contains(vendor_id)
+    }
+
+    pub fn has_special_feature_opt_in<F: Into<FeatureId>>(&self, feature_id: F) -> bool {
+        self.special_feature_opt_ins.contains(feature_id.into())
+    }
+
+    pub fn has_vendor_legitimate_interest<V: Into<VendorId>>(&self, vendor_id: V) -> bool {
+        let vendor_id = vendor_id.into();
+        vendor_id.0 as usize <= self.max_vendor_legitimate_interest_id
+            && self.vendor_legitimate_interest.contains(vendor_id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum VendorConsent {
     V1(V1),
+    V2(V2),
 }
 
 impl VendorConsent {
     pub fn to_string(&self) -> Result<String, Error> {
         match self {
-            VendorConsent::V1(ref v1) => serialize_v1(v1),
+            VendorConsent::V1(ref v1) => v1.to_string(),
+            VendorConsent::V2(ref v2) => v2.to_string(),
+        }
+    }
+
+    pub fn has_purpose_consent<P: Into<PurposeId>>(&self, purpose_id: P) -> bool {
+        match self {
+            VendorConsent::V1(ref v1) => v1.has_purpose_consent(purpose_id),
+            VendorConsent::V2(ref v2) => v2.has_purpose_consent(purpose_id),
         }
     }
+
+    pub fn has_vendor_consent<V: Into<VendorId>>(&self, vendor_id: V) -> bool {
+        match self {
+            VendorConsent::V1(ref v1) => v1.has_vendor_consent(vendor_id),
+            VendorConsent::V2(ref v2) => v2.has_vendor_consent(vendor_id),
+        }
+    }
+
+    // v1 consent strings have no special features, so are never opted in
+    pub fn has_special_feature_opt_in<F: Into<FeatureId>>(&self, feature_id: F) -> bool {
+        match self {
+            VendorConsent::V1(_) => false,
+            VendorConsent::V2(ref v2) => v2.has_special_feature_opt_in(feature_id),
+        }
+    }
+
+    pub fn created(&self) -> Deciseconds {
+        match self {
+            VendorConsent::V1(ref v1) => v1.created,
+            VendorConsent::V2(ref v2) => v2.created,
+        }
+    }
+
+    pub fn last_updated(&self) -> Deciseconds {
+        match self {
+            VendorConsent::V1(ref v1) => v1.last_updated,
+            VendorConsent::V2(ref v2) => v2.last_updated,
+        }
+    }
+
+    pub fn cmp_id(&self) -> u16 {
+        match self {
+            VendorConsent::V1(ref v1) => v1.cmp_id,
+            VendorConsent::V2(ref v2) => v2.cmp_id,
+        }
+    }
+
+    pub fn cmp_version(&self) -> u16 {
+        match self {
+            VendorConsent::V1(ref v1) => v1.cmp_version,
+            VendorConsent::V2(ref v2) => v2.cmp_version,
+        }
+    }
+
+    pub fn consent_screen(&self) -> u8 {
+        match self {
+            VendorConsent::V1(ref v1) => v1.consent_screen,
+            VendorConsent::V2(ref v2) => v2.consent_screen,
+        }
+    }
+
+    pub fn consent_language(&self) -> &str {
+        match self {
+            VendorConsent::V1(ref v1) => &v1.consent_language,
+            VendorConsent::V2(ref v2) => &v2.consent_language,
+        }
+    }
+
+    pub fn vendor_list_version(&self) -> u16 {
+        match self {
+            VendorConsent::V1(ref v1) => v1.vendor_list_version,
+            VendorConsent::V2(ref v2) => v2.vendor_list_version,
+        }
+    }
+
+    pub fn max_vendor_id(&self) -> usize {
+        match self {
+            VendorConsent::V1(ref v1) => v1.max_vendor_id,
+            VendorConsent::V2(ref v2) => v2.max_vendor_id,
+        }
+    }
+}
+
+// Checks applied on top of what the consent string format itself enforces.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationOptions {
+    // Reject timestamps later than this, e.g. `Deciseconds::now()` to reject
+    // consent strings from the future.
+    pub latest_timestamp: Option<Deciseconds>,
+
+    // Reject consent strings last updated before they were created
+    pub reject_last_updated_before_created: bool,
+
+    // Reject consent strings from CMPs missing from this list, or created
+    // after the CMP was deleted from it
+    #[cfg(feature = "std")]
+    pub cmp_list: Option<Arc<CmpList>>,
+
+    // Reject consent strings whose vendor list version is missing from the
+    // archive, is for another TCF version, or was published after the
+    // consent string was last updated
+    #[cfg(feature = "archive")]
+    pub vendor_list_archive: Option<Arc<VendorListArchive>>,
+}
+
+impl ValidationOptions {
+    // Rejects future timestamps and last_updated < created.
+    #[cfg(feature = "std")]
+    pub fn strict() -> ValidationOptions {
+        ValidationOptions {
+            latest_timestamp: Some(Deciseconds::now()),
+            reject_last_updated_before_created: true,
+            cmp_list: None,
+            #[cfg(feature = "archive")]
+            vendor_list_archive: None,
+        }
+    }
+}
+
+impl VendorConsent {
+    pub fn parse_with(s: &str, options: &ValidationOptions) -> Result<VendorConsent, Error> {
+        let consent: VendorConsent = s.parse()?;
+        consent.validate(options)?;
+        Ok(consent)
+    }
+
+    pub fn validate(&self, options: &ValidationOptions) -> Result<(), Error> {
+        let (created, last_updated) = (self.created(), self.last_updated());
+
+        if let Some(latest) = options.latest_timestamp {
+            for (name, t) in &[("created", created), ("last updated", last_updated)] {
+                if *t > latest {
+                    return Err(Error::Other(format!(
+                        "Consent string {} timestamp {} is after {}",
+                        name, t, latest
+                    )));
+                }
+            }
+        }
+
+        if options.reject_last_updated_before_created && last_updated < created {
+            return Err(Error::Other(format!(
+                "Consent string last updated {} before it was created {}",
+                last_updated, created
+            )));
+        }
+
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref cmp_list) = options.cmp_list {
+                match cmp_list.status(self.cmp_id(), created) {
+                    CmpStatus::Registered => {}
+                    CmpStatus::Unknown => {
+                        return Err(Error::Other(format!("Unknown CMP ID {}", self.cmp_id())));
+                    }
+                    CmpStatus::Deleted(deleted) => {
+                        return Err(Error::Other(format!(
+                            "CMP ID {} was deleted on {}, before consent was created {}",
+                            self.cmp_id(),
+                            deleted.to_rfc3339(),
+                            created
+                        )));
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "archive")]
+        {
+            if let Some(ref archive) = options.vendor_list_archive {
+                let version = self.vendor_list_version();
+                let vendor_list = archive
+                    .get(version)
+                    .map_err(|e| Error::Other(format!("Vendor list version {}: {}", version, e)))?
+                    .ok_or_else(|| {
+                        Error::Other(format!("Unknown vendor list version {}", version))
+                    })?;
+
+                let is_v2 = match self {
+                    VendorConsent::V1(_) => false,
+                    VendorConsent::V2(_) => true,
+                };
+                if vendor_list.is_v2() != is_v2 {
+                    return Err(Error::Other(format!(
+                        "Vendor list version {} is not for this consent string's TCF version",
+                        version
+                    )));
+                }
+                if vendor_list.last_updated > last_updated.to_datetime() {
+                    return Err(Error::Other(format!(
+                        "Vendor list version {} was published {}, after consent was last updated {}",
+                        version,
+                        vendor_list.last_updated.to_rfc3339(),
+                        last_updated
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
     Base64DecodeError(base64::DecodeError),
     UnsupportedVersion(u8),
-    IoError(io::Error),
+    UnexpectedEof,
     FromUtf8Error(string::FromUtf8Error),
     Other(String),
 }
 
+#[cfg(feature = "std")]
 impl error::Error for Error {
-    fn description(&self) -> &str {
-        match self {
-            Error::Base64DecodeError(ref err) => err.description(),
-            Error::UnsupportedVersion(_) => "Unsupported version",
-            Error::IoError(ref err) => err.description(),
-            Error::FromUtf8Error(ref err) => err.description(),
-            Error::Other(msg) => msg,
-        }
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::Base64DecodeError(ref err) => Some(err),
             Error::UnsupportedVersion(_) => None,
-            Error::IoError(ref err) => Some(err),
+            Error::UnexpectedEof => None,
             Error::FromUtf8Error(ref err) => Some(err),
             Error::Other(_) => None,
         }
@@ -101,19 +470,13 @@ impl Display for Error {
         match self {
             Error::Base64DecodeError(ref err) => Display::fmt(err, f),
             Error::UnsupportedVersion(v) => write!(f, "Unsupported version: {}", v),
-            Error::IoError(ref err) => Display::fmt(err, f),
+            Error::UnexpectedEof => f.write_str("Unexpected end of consent string"),
             Error::FromUtf8Error(ref err) => Display::fmt(err, f),
             Error::Other(msg) => Display::fmt(msg, f),
         }
     }
 }
 
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Error {
-        Error::IoError(e)
-    }
-}
-
 impl From<string::FromUtf8Error> for Error {
     fn from(e: string::FromUtf8Error) -> Error {
         Error::FromUtf8Error(e)
@@ -132,192 +495,434 @@ enum Entry {
     Range(usize, usize),
 }
 
-fn parse_v1_bitfield<R>(
-    mut reader: BitReader<R, BigEndian>,
-    max_vendor_id: usize,
-) -> Result<BitSet, Error>
-where R: io::Read
-{
-    let buf_size = max_vendor_id / 8 + if max_vendor_id % 8 == 0 { 0 } else { 1 };
-    let mut buf = Vec::with_capacity(buf_size);
+fn read_bitfield(reader: &mut BitReader, len: usize) -> Result<BitSet, Error> {
+    let buf_size = len / 8;
+    let mut buf = Vec::with_capacity(buf_size + 1);
 
     // read full bytes
     for _ in 0..buf_size {
-        buf.push(reader.read::<u8>(8)?);
+        buf.push(reader.read_bits::<u8>(8)?);
     }
 
     // read remainder
-    if max_vendor_id % 8 > 0 {
-        buf.push(reader.read::<u8>(max_vendor_id as u32 % 8)?);
+    let remainder = len as u32 % 8;
+    if remainder > 0 {
+        buf.push(reader.read_bits::<u8>(remainder)? << (8 - remainder));
     }
 
     Ok(BitSet::from_bytes(&buf))
 }
 
-fn parse_v1_range<R>(
-    mut reader: BitReader<R, BigEndian>,
-    max_vendor_id: usize,
-) -> Result<BitSet, Error>
-where R: io::Read
-{
-    let default_consent = reader.read::<u8>(1)? == 1;
-    let num_entries = reader.read::<u16>(12)? as usize;
+fn parse_v1_range(reader: &mut BitReader, max_vendor_id: usize) -> Result<BitSet, Error> {
+    let default_consent = reader.read_bit()?;
+    let num_entries = reader.read_bits::<usize>(12)?;
 
     let mut buf = BitVec::from_elem(max_vendor_id, default_consent);
     for _ in 0..num_entries {
-        match reader.read::<u8>(1)? {
-            0 => {
-                let id = reader.read::<u16>(16)? as usize;
-                buf.set(id - 1, !default_consent)
-            }
-            _ => {
-                let start = reader.read::<u16>(16)? as usize;
-                let end = reader.read::<u16>(16)? as usize;
-                for id in start..=end {
-                    buf.set(id - 1, !default_consent);
-                }
-            }
+        let is_range = reader.read_bit()?;
+        let start = reader.read_bits::<usize>(16)?;
+        let end = if is_range {
+            reader.read_bits::<usize>(16)?
+        } else {
+            start
+        };
+
+        if start == 0 || end < start {
+            return Err(Error::Other(format!(
+                "Invalid vendor range: {}-{}",
+                start, end
+            )));
+        }
+        if end > max_vendor_id {
+            return Err(Error::Other(format!(
+                "Vendor range exceeds max vendor ID {}",
+                max_vendor_id
+            )));
+        }
+
+        for id in start..=end {
+            buf.set(id - 1, !default_consent);
         }
     }
 
     Ok(BitSet::from_bit_vec(buf))
 }
 
-const DECISECS_IN_SEC: i64 = 10;
-const MILLISECS_IN_DECISEC: u32 = 100;
-const NANOSECS_IN_DECISEC: u32 = 100_000_000;
-
-fn parse_v1<R>(mut reader: BitReader<R, BigEndian>) -> Result<V1, Error>
-where R: io::Read
-{
-    let created = reader.read::<i64>(36)?;
-    let created = Utc.timestamp(
-        created / DECISECS_IN_SEC,
-        (created % DECISECS_IN_SEC) as u32 * NANOSECS_IN_DECISEC,
-    );
-    let last_updated = reader.read::<i64>(36)?;
-    let last_updated = Utc.timestamp(
-        last_updated / DECISECS_IN_SEC,
-        (last_updated % DECISECS_IN_SEC) as u32 * NANOSECS_IN_DECISEC,
-    );
-    let cmp_id = reader.read::<u16>(12)?;
-    let cmp_version = reader.read::<u16>(12)?;
-    let consent_screen = reader.read::<u8>(6)?;
-
-    let mut buf = Vec::with_capacity(2);
-    for _ in 0..2 {
-        buf.push(reader.read::<u8>(6)? + 'a' as u8);
-    }
-    let consent_language = String::from_utf8(buf)?;
-
-    let vendor_list_version = reader.read::<u16>(12)?;
-
-    let mut buf: [u8; 3] = Default::default();
-    reader.read_bytes(&mut buf)?;
-    let purposes_allowed = BitSet::from_bytes(&buf);
-
-    let max_vendor_id = reader.read::<u16>(16)? as usize;
-
-    let vendor_consent = match reader.read::<u8>(1)? {
-        0 => parse_v1_bitfield(reader, max_vendor_id)?,
-        _ => parse_v1_range(reader, max_vendor_id)?,
-    };
+fn read_timestamp(reader: &mut BitReader) -> Result<Deciseconds, Error> {
+    Deciseconds::new(reader.read_bits::<u64>(36)?)
+}
+
+fn read_letters(reader: &mut BitReader, base: u8) -> Result<String, Error> {
+    let letters = [reader.read_letter(base)?, reader.read_letter(base)?];
+    String::from_utf8(letters.to_vec()).map_err(From::from)
+}
+
+fn parse_v1(mut reader: BitReader) -> Result<V1, Error> {
+    let created = read_timestamp(&mut reader)?;
+    let last_updated = read_timestamp(&mut reader)?;
+    let cmp_id = reader.read_bits::<u16>(12)?;
+    let cmp_version = reader.read_bits::<u16>(12)?;
+    let consent_screen = reader.read_bits::<u8>(6)?;
+    let consent_language = read_letters(&mut reader, b'a')?;
+    let vendor_list_version = reader.read_bits::<u16>(12)?;
+    let purposes_allowed = PurposeSet::from_bit_set(read_bitfield(&mut reader, 24)?);
+
+    let max_vendor_id = reader.read_bits::<usize>(16)?;
+
+    let vendor_consent = VendorSet::from_bit_set(match reader.read_bit()? {
+        false => read_bitfield(&mut reader, max_vendor_id)?,
+        true => parse_v1_range(&mut reader, max_vendor_id)?,
+    });
 
     Ok(V1 {
-        created: created,
-        last_updated: last_updated,
-        cmp_id: cmp_id,
-        cmp_version: cmp_version,
-        consent_screen: consent_screen,
-        consent_language: consent_language,
-        vendor_list_version: vendor_list_version,
-        purposes_allowed: purposes_allowed,
-        max_vendor_id: max_vendor_id,
-        vendor_consent: vendor_consent,
+        created,
+        last_updated,
+        cmp_id,
+        cmp_version,
+        consent_screen,
+        consent_language,
+        vendor_list_version,
+        purposes_allowed,
+        max_vendor_id,
+        vendor_consent,
+    })
+}
+
+fn read_v2_range(reader: &mut BitReader) -> Result<VendorSet, Error> {
+    let num_entries = reader.read_bits::<u16>(12)?;
+
+    let mut vendors = VendorSet::new();
+    for _ in 0..num_entries {
+        let is_range = reader.read_bit()?;
+        let start = reader.read_bits::<u16>(16)?;
+        let end = if is_range {
+            reader.read_bits::<u16>(16)?
+        } else {
+            start
+        };
+
+        if start == 0 || end < start {
+            return Err(Error::Other(format!(
+                "Invalid vendor range: {}-{}",
+                start, end
+            )));
+        }
+
+        vendors.extend((start..=end).map(VendorId));
+    }
+
+    Ok(vendors)
+}
+
+fn parse_v2_vendors(reader: &mut BitReader) -> Result<(usize, VendorSet), Error> {
+    let max_vendor_id = reader.read_bits::<usize>(16)?;
+
+    let vendors = if reader.read_bit()? {
+        let vendors = read_v2_range(reader)?;
+        if vendors
+            .max()
+            .is_some_and(|id| id.0 as usize > max_vendor_id)
+        {
+            return Err(Error::Other(format!(
+                "Vendor range exceeds max vendor ID {}",
+                max_vendor_id
+            )));
+        }
+        vendors
+    } else {
+        VendorSet::from_bit_set(read_bitfield(reader, max_vendor_id)?)
+    };
+
+    Ok((max_vendor_id, vendors))
+}
+
+fn parse_v2(mut reader: BitReader) -> Result<V2, Error> {
+    let created = read_timestamp(&mut reader)?;
+    let last_updated = read_timestamp(&mut reader)?;
+    let cmp_id = reader.read_bits::<u16>(12)?;
+    let cmp_version = reader.read_bits::<u16>(12)?;
+    let consent_screen = reader.read_bits::<u8>(6)?;
+    let consent_language = read_letters(&mut reader, b'A')?;
+    let vendor_list_version = reader.read_bits::<u16>(12)?;
+    let tcf_policy_version = reader.read_bits::<u8>(6)?;
+    let is_service_specific = reader.read_bit()?;
+    let use_non_standard_stacks = reader.read_bit()?;
+    let special_feature_opt_ins = FeatureSet::from_bit_set(read_bitfield(&mut reader, 12)?);
+    let purposes_consent = PurposeSet::from_bit_set(read_bitfield(&mut reader, 24)?);
+    let purposes_li_transparency = PurposeSet::from_bit_set(read_bitfield(&mut reader, 24)?);
+    let purpose_one_treatment = reader.read_bit()?;
+    let publisher_cc = read_letters(&mut reader, b'A')?;
+
+    let (max_vendor_id, vendor_consent) = parse_v2_vendors(&mut reader)?;
+    let (max_vendor_legitimate_interest_id, vendor_legitimate_interest) =
+        parse_v2_vendors(&mut reader)?;
+
+    let num_restrictions = reader.read_bits::<u16>(12)?;
+    let mut publisher_restrictions = Vec::with_capacity(num_restrictions as usize);
+    for _ in 0..num_restrictions {
+        let purpose_id = PurposeId(reader.read_bits::<u8>(6)?);
+        let restriction_type = match reader.read_bits::<u8>(2)? {
+            0 => RestrictionType::NotAllowed,
+            1 => RestrictionType::RequireConsent,
+            2 => RestrictionType::RequireLegitimateInterest,
+            t => return Err(Error::Other(format!("Invalid restriction type: {}", t))),
+        };
+        let vendors = read_v2_range(&mut reader)?;
+
+        publisher_restrictions.push(PublisherRestriction {
+            purpose_id,
+            restriction_type,
+            vendors,
+        });
+    }
+
+    Ok(V2 {
+        created,
+        last_updated,
+        cmp_id,
+        cmp_version,
+        consent_screen,
+        consent_language,
+        vendor_list_version,
+        tcf_policy_version,
+        is_service_specific,
+        use_non_standard_stacks,
+        special_feature_opt_ins,
+        purposes_consent,
+        purposes_li_transparency,
+        purpose_one_treatment,
+        publisher_cc,
+        max_vendor_id,
+        vendor_consent,
+        max_vendor_legitimate_interest_id,
+        vendor_legitimate_interest,
+        publisher_restrictions,
+        segments: Vec::new(),
     })
 }
 
+// Decodes a web-safe (or standard) base64 segment, returning its web-safe form
+// without padding along with the decoded bytes
+fn decode_segment(segment: &str) -> Result<(String, Vec<u8>), Error> {
+    let segment = segment
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_");
+    let data = base64::decode_config(&segment, base64::URL_SAFE_NO_PAD)?;
+    Ok((segment, data))
+}
+
 impl FromStr for VendorConsent {
     type Err = Error;
 
+    // Consent strings are web-safe base64, but the standard alphabet is accepted as well.
+    // For TCF v2 strings, only the core segment (up to the first '.') is decoded; the
+    // remaining segments are kept as they are. v1 strings have no further segments.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let data = base64::decode(s)?;
-        let mut cursor = io::Cursor::new(&data);
-        let mut reader = BitReader::endian(&mut cursor, BigEndian);
+        let mut segments = s.split('.');
+        let (_, data) = decode_segment(segments.next().unwrap_or_default())?;
+        let mut reader = BitReader::new(&data);
 
-        let version = reader.read::<u8>(6)?;
+        let version = reader.read_bits::<u8>(6)?;
         match version {
-            1 => parse_v1(reader).map(VendorConsent::V1),
+            1 => {
+                if segments.next().is_some() {
+                    return Err(Error::Other(String::from(
+                        "v1 consent strings have a single segment",
+                    )));
+                }
+                parse_v1(reader).map(VendorConsent::V1)
+            }
+            2 => {
+                let mut v2 = parse_v2(reader)?;
+                v2.segments = segments
+                    .map(|segment| decode_segment(segment).map(|(segment, _)| segment))
+                    .collect::<Result<_, _>>()?;
+                Ok(VendorConsent::V2(v2))
+            }
             v => Err(Error::UnsupportedVersion(v)),
         }
     }
 }
 
-fn serialize_v1(v: &V1) -> Result<String, Error> {
-    if v.consent_language.len() != 2 {
-        return Err(Error::Other(format!(
-            "Invalid consent language: {}",
-            v.consent_language
-        )));
+fn check_letters(name: &str, value: &str, valid: fn(&u8) -> bool) -> Result<(), Error> {
+    if value.len() != 2 {
+        return Err(Error::Other(format!("Invalid {}: {}", name, value)));
     }
 
-    let language_bytes = v.consent_language.as_bytes();
-    for i in 0..=1 {
-        if language_bytes[i] < ('a' as u8) || language_bytes[i] > ('z' as u8) {
+    for (i, b) in value.as_bytes().iter().enumerate() {
+        if !valid(b) {
             return Err(Error::Other(format!(
-                "Invalid char '{}' in consent language at position {}",
-                language_bytes[i] as char, i
+                "Invalid char '{}' in {} at position {}",
+                *b as char, name, i
             )));
         }
     }
 
+    Ok(())
+}
+
+fn serialize_v1(v: &V1, encoding: Encoding) -> Result<String, Error> {
+    check_letters(
+        "consent language",
+        &v.consent_language,
+        u8::is_ascii_lowercase,
+    )?;
+    let language_bytes = v.consent_language.as_bytes();
+
     // default to true if more than half of bits are set
     let default_consent = v.vendor_consent.len() >= v.max_vendor_id / 2;
     let (range, range_encoded_len) = match default_consent {
-        false => create_true_range(&v.vendor_consent),
-        true => create_false_range(&v.vendor_consent, v.max_vendor_id),
+        false => create_true_range(v.vendor_consent.as_bit_set()),
+        true => create_false_range(v.vendor_consent.as_bit_set(), v.max_vendor_id),
     };
 
-    // choose smaller encoding
-    let encoding_type = if v.max_vendor_id <= range_encoded_len {
-        0
-    } else {
-        1
+    let encoding_type = match encoding {
+        Encoding::Bitfield => 0,
+        Encoding::Range => 1,
+        // choose smaller encoding
+        Encoding::Shortest if v.max_vendor_id <= range_encoded_len => 0,
+        Encoding::Shortest => 1,
     };
 
-    let mut raw = Vec::new();
+    let mut writer = BitWriter::new();
+    writer.write(6, 1)?;
+    writer.write(36, v.created.as_u64())?;
+    writer.write(36, v.last_updated.as_u64())?;
+    writer.write(12, v.cmp_id as u64)?;
+    writer.write(12, v.cmp_version as u64)?;
+    writer.write(6, v.consent_screen as u64)?;
+    for b in language_bytes {
+        writer.write_letter(b'a', *b)?;
+    }
+    writer.write(12, v.vendor_list_version as u64)?;
+    write_bitfield(&mut writer, v.purposes_allowed.as_bit_set(), 24)?;
+    writer.write(16, v.max_vendor_id as u64)?;
+    writer.write(1, encoding_type as u64)?;
+    if encoding_type == 0 {
+        write_bitfield(&mut writer, v.vendor_consent.as_bit_set(), v.max_vendor_id)?;
+    } else {
+        writer.write_bit(default_consent);
+        write_range(&mut writer, range)?;
+    }
+
+    let raw = writer.into_bytes();
+    Ok(base64::encode_config(&raw, base64::URL_SAFE_NO_PAD))
+}
+
+fn serialize_v2(v: &V2) -> Result<String, Error> {
+    check_letters(
+        "consent language",
+        &v.consent_language,
+        u8::is_ascii_uppercase,
+    )?;
+    check_letters(
+        "publisher country code",
+        &v.publisher_cc,
+        u8::is_ascii_uppercase,
+    )?;
+
+    let mut writer = BitWriter::new();
+    writer.write(6, 2)?;
+    writer.write(36, v.created.as_u64())?;
+    writer.write(36, v.last_updated.as_u64())?;
+    writer.write(12, v.cmp_id as u64)?;
+    writer.write(12, v.cmp_version as u64)?;
+    writer.write(6, v.consent_screen as u64)?;
+    for b in v.consent_language.as_bytes() {
+        writer.write_letter(b'A', *b)?;
+    }
+    writer.write(12, v.vendor_list_version as u64)?;
+    writer.write(6, v.tcf_policy_version as u64)?;
+    writer.write_bit(v.is_service_specific);
+    writer.write_bit(v.use_non_standard_stacks);
+    write_bitfield(&mut writer, v.special_feature_opt_ins.as_bit_set(), 12)?;
+    write_bitfield(&mut writer, v.purposes_consent.as_bit_set(), 24)?;
+    write_bitfield(&mut writer, v.purposes_li_transparency.as_bit_set(), 24)?;
+    writer.write_bit(v.purpose_one_treatment);
+    for b in v.publisher_cc.as_bytes() {
+        writer.write_letter(b'A', *b)?;
+    }
+
+    write_v2_vendors(&mut writer, &v.vendor_consent, v.max_vendor_id)?;
+    write_v2_vendors(
+        &mut writer,
+        &v.vendor_legitimate_interest,
+        v.max_vendor_legitimate_interest_id,
+    )?;
+
+    writer.write(12, v.publisher_restrictions.len() as u64)?;
+    for restriction in &v.publisher_restrictions {
+        writer.write(6, restriction.purpose_id.0 as u64)?;
+        writer.write(2, restriction.restriction_type.as_u8() as u64)?;
+        let (range, _) = create_true_range(restriction.vendors.as_bit_set());
+        write_range(&mut writer, range)?;
+    }
+
+    let raw = writer.into_bytes();
+    let mut encoded = base64::encode_config(&raw, base64::URL_SAFE_NO_PAD);
+    for segment in &v.segments {
+        encoded.push('.');
+        encoded.push_str(segment);
+    }
+    Ok(encoded)
+}
+
+fn write_bitfield(writer: &mut BitWriter, bits: &BitSet, len: usize) -> Result<(), Error> {
+    for i in 0..len {
+        writer.write_bit(bits.contains(i));
+    }
+
+    Ok(())
+}
+
+fn write_v2_vendors(
+    writer: &mut BitWriter,
+    vendors: &VendorSet,
+    max_vendor_id: usize,
+) -> Result<(), Error> {
+    if vendors
+        .max()
+        .is_some_and(|id| id.0 as usize > max_vendor_id)
     {
-        let mut writer = BitWriter::endian(&mut raw, BigEndian);
-        writer.write(6, 1)?;
-        writer.write(
-            36,
-            v.created.timestamp() * 10
-                + (v.created.timestamp_subsec_millis() / MILLISECS_IN_DECISEC) as i64,
-        )?;
-        writer.write(
-            36,
-            v.last_updated.timestamp() * 10
-                + (v.last_updated.timestamp_subsec_millis() / MILLISECS_IN_DECISEC) as i64,
-        )?;
-        writer.write(12, v.cmp_id)?;
-        writer.write(12, v.cmp_version)?;
-        writer.write(6, v.consent_screen)?;
-        for b in language_bytes {
-            writer.write(6, b - ('a' as u8))?;
-        }
-        writer.write(12, v.vendor_list_version)?;
-        writer.write_bytes(&v.purposes_allowed.get_ref().to_bytes())?;
-        writer.write(16, v.max_vendor_id as u16)?;
-        writer.write(1, encoding_type)?;
-        if encoding_type == 0 {
-            writer.write_bytes(&v.vendor_consent.get_ref().to_bytes())?;
-            writer.byte_align()?;
-        } else {
-            encode_range(writer, default_consent, range)?;
+        return Err(Error::Other(format!(
+            "Vendor IDs exceed max vendor ID {}",
+            max_vendor_id
+        )));
+    }
+
+    // v2 range encoding has no default consent bit
+    let (range, range_encoded_len) = create_true_range(vendors.as_bit_set());
+
+    writer.write(16, max_vendor_id as u64)?;
+    if max_vendor_id < range_encoded_len {
+        writer.write_bit(false);
+        write_bitfield(writer, vendors.as_bit_set(), max_vendor_id)
+    } else {
+        writer.write_bit(true);
+        write_range(writer, range)
+    }
+}
+
+// Entry count and entries of a vendor range, after the v1 default consent bit
+fn write_range(writer: &mut BitWriter, range: Vec<Entry>) -> Result<(), Error> {
+    writer.write(12, range.len() as u64)?;
+
+    for e in range {
+        match e {
+            Entry::Single(x) => {
+                writer.write_bit(false);
+                writer.write(16, x as u64)?;
+            }
+            Entry::Range(s, e) => {
+                writer.write_bit(true);
+                writer.write(16, s as u64)?;
+                writer.write(16, e as u64)?;
+            }
         }
     }
 
-    Ok(base64::encode_config(&raw, base64::URL_SAFE_NO_PAD))
+    Ok(())
 }
 
 fn create_true_range(vendor_consent: &BitSet) -> (Vec<Entry>, usize) {
@@ -348,12 +953,12 @@ fn create_true_range(vendor_consent: &BitSet) -> (Vec<Entry>, usize) {
     }
 
     // close range if open
-    if !start.is_none() {
+    if let (Some(start), Some(end)) = (start, end) {
         if start == end {
-            range.push(Entry::Single(start.unwrap() + 1));
+            range.push(Entry::Single(start + 1));
             count += 17; // 1 + 16
         } else {
-            range.push(Entry::Range(start.unwrap() + 1, end.unwrap() + 1));
+            range.push(Entry::Range(start + 1, end + 1));
             count += 33; // 1 + 16 + 16
         }
     }
@@ -367,44 +972,18 @@ fn create_false_range(vendor_consent: &BitSet, max_vendor_id: usize) -> (Vec<Ent
     create_true_range(&inverse)
 }
 
-fn encode_range<W>(
-    mut writer: BitWriter<W, BigEndian>,
-    default_consent: bool,
-    range: Vec<Entry>,
-) -> Result<(), Error>
-where W: io::Write
-{
-    writer.write_bit(default_consent)?;
-    writer.write(12, range.len() as u16)?;
-
-    for e in range {
-        match e {
-            Entry::Single(x) => {
-                writer.write(1, 0)?;
-                writer.write(16, x as u16)?;
-            }
-            Entry::Range(s, e) => {
-                writer.write(1, 1)?;
-                writer.write(16, s as u16)?;
-                writer.write(16, e as u16)?;
-            }
-        }
-    }
-
-    writer.byte_align()?;
-
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+
     #[test]
     fn serialize_good() {
         let mut vendor_consent = BitVec::from_elem(2011, true);
         vendor_consent.set(8, false);
-        let vendor_consent = BitSet::from_bit_vec(vendor_consent);
+        let vendor_consent = VendorSet::from_bit_set(BitSet::from_bit_vec(vendor_consent));
 
         let v = VendorConsent::V1(V1 {
             created: "2017-11-07T19:15:55.4Z".parse().unwrap(),
@@ -414,9 +993,11 @@ mod tests {
             consent_screen: 3,
             consent_language: "en".to_string(),
             vendor_list_version: 8,
-            purposes_allowed: BitSet::from_bytes(&[0b11100000, 0b00000000, 0b00000000]),
+            purposes_allowed: PurposeSet::from_bit_set(BitSet::from_bytes(&[
+                0b11100000, 0b00000000, 0b00000000,
+            ])),
             max_vendor_id: 2011,
-            vendor_consent: vendor_consent,
+            vendor_consent,
         });
 
         let serialized = v.to_string().unwrap();
@@ -424,21 +1005,56 @@ mod tests {
         assert_eq!(serialized, expected);
     }
 
+    #[test]
+    fn serialize_v1_encodings() {
+        let v1 = || match "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".parse().unwrap() {
+            VendorConsent::V1(v1) => v1,
+            _ => panic!("expected a v1 consent string"),
+        };
+        let v = v1();
+        assert_eq!(
+            v.to_string_with(Encoding::Shortest).unwrap(),
+            v.to_string().unwrap()
+        );
+        assert_eq!(
+            v.to_string_with(Encoding::Range).unwrap(),
+            v.to_string().unwrap()
+        );
+
+        let bitfield = v.to_string_with(Encoding::Bitfield).unwrap();
+        assert!(bitfield.len() > 2011 / 6);
+        assert_eq!(
+            bitfield.parse::<VendorConsent>().unwrap(),
+            VendorConsent::V1(v)
+        );
+
+        // every other vendor takes a range entry, more than the 12-bit count holds
+        let sparse = V1 {
+            max_vendor_id: 9000,
+            vendor_consent: (0..4500).map(|i| VendorId(i * 2 + 1)).collect(),
+            ..v1()
+        };
+        assert!(sparse.to_string_with(Encoding::Range).is_err());
+        assert!(sparse.to_string().is_ok());
+    }
+
     #[test]
     fn deserialize_good() {
         let v = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".parse().unwrap();
 
-        let expected_purposes_allowed = BitSet::from_bytes(&[0b11100000, 0b00000000, 0b00000000]);
+        let expected_purposes_allowed =
+            PurposeSet::from_bit_set(BitSet::from_bytes(&[0b11100000, 0b00000000, 0b00000000]));
 
         let expected_max_vendor_id = 2011;
         let mut expected_vendor_consent = BitVec::from_elem(expected_max_vendor_id, true);
         expected_vendor_consent.set(8, false);
-        let expected_vendor_consent = BitSet::from_bit_vec(expected_vendor_consent);
+        let expected_vendor_consent =
+            VendorSet::from_bit_set(BitSet::from_bit_vec(expected_vendor_consent));
 
         match v {
             VendorConsent::V1(v1) => {
-                assert_eq!(Ok(v1.created), "2017-11-07T19:15:55.4Z".parse());
-                assert_eq!(Ok(v1.last_updated), "2017-11-07T19:15:55.4Z".parse());
+                assert_eq!(v1.created.to_string(), "2017-11-07T19:15:55.400+00:00");
+                assert_eq!(v1.last_updated.as_u64(), 15100821554);
                 assert_eq!(v1.cmp_id, 7);
                 assert_eq!(v1.cmp_version, 1);
                 assert_eq!(v1.consent_screen, 3);
@@ -448,6 +1064,161 @@ mod tests {
                 assert_eq!(v1.max_vendor_id, expected_max_vendor_id);
                 assert_eq!(v1.vendor_consent, expected_vendor_consent);
             }
+            v => panic!("Unexpected consent: {:?}", v),
+        }
+    }
+
+    #[test]
+    fn deserialize_invalid_v1_range() {
+        // single entry with vendor ID 0
+        assert!("BOEFEAyOEFEAyAHABDAAAIAAAAAAWABAAAA"
+            .parse::<VendorConsent>()
+            .is_err());
+        // single entry above the max vendor ID
+        assert!("BOEFEAyOEFEAyAHABDAAAIAAAAAAWABAAUA"
+            .parse::<VendorConsent>()
+            .is_err());
+    }
+
+    #[test]
+    fn deserialize_v1_partial_bitfield_byte() {
+        // bitfield for max vendor ID 10 with only vendor 10 consented, so the
+        // last byte holds just two bits
+        let v: VendorConsent = "BOEFEAyOEFEAyAHABDENAIAAAAAAoAI".parse().unwrap();
+
+        match v {
+            VendorConsent::V1(v1) => {
+                assert_eq!(v1.max_vendor_id, 10);
+                assert_eq!(v1.vendor_consent, [VendorId(10)].iter().cloned().collect());
+                assert!(!v1.has_vendor_consent(9));
+            }
+            _ => panic!("expected v1"),
+        }
+    }
+
+    #[test]
+    fn deserialize_v2_good() {
+        let v = "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA.IFoEUQQgAIQwgIwQABAEAAAAOIAACAIAAAAQAIAgEAACEAAAAAgAQBAAAAAAAGBAAgAAAAAAAFAAECAAAgAAQARAEQAAAAAJAAIAAgAAAYQEAAAQmAgBC3ZAYzUw"
+            .parse()
+            .unwrap();
+
+        let expected_vendors: VendorSet = [2, 6, 8].iter().cloned().map(VendorId).collect();
+
+        match v {
+            VendorConsent::V2(v2) => {
+                assert_eq!(v2.created.to_datetime().timestamp(), 1582243059);
+                assert_eq!(v2.last_updated.to_datetime().timestamp(), 1582243059);
+                assert_eq!(v2.cmp_id, 27);
+                assert_eq!(v2.cmp_version, 0);
+                assert_eq!(v2.consent_screen, 0);
+                assert_eq!(v2.consent_language, "EN");
+                assert_eq!(v2.vendor_list_version, 15);
+                assert_eq!(v2.tcf_policy_version, 2);
+                assert!(!v2.is_service_specific);
+                assert!(!v2.use_non_standard_stacks);
+                assert!(v2.special_feature_opt_ins.is_empty());
+                assert_eq!(
+                    v2.purposes_consent.iter().collect::<Vec<_>>(),
+                    vec![PurposeId(1), PurposeId(2), PurposeId(3)]
+                );
+                assert!(v2.purposes_li_transparency.is_empty());
+                assert!(!v2.purpose_one_treatment);
+                assert_eq!(v2.publisher_cc, "AA");
+                assert_eq!(v2.max_vendor_id, 8);
+                assert_eq!(v2.vendor_consent, expected_vendors);
+                assert_eq!(v2.max_vendor_legitimate_interest_id, 8);
+                assert_eq!(v2.vendor_legitimate_interest, expected_vendors);
+                assert!(v2.publisher_restrictions.is_empty());
+            }
+            v => panic!("Unexpected consent: {:?}", v),
+        }
+    }
+
+    #[test]
+    fn serialize_v2_round_trip() {
+        let s = "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA";
+        let v: VendorConsent = s.parse().unwrap();
+        assert_eq!(v.to_string().unwrap(), s);
+
+        let v = VendorConsent::V2(V2 {
+            created: "2020-02-21T00:00:00.5Z".parse().unwrap(),
+            last_updated: "2020-03-01T12:00:00Z".parse().unwrap(),
+            cmp_id: 300,
+            cmp_version: 2,
+            consent_screen: 1,
+            consent_language: "DE".to_string(),
+            vendor_list_version: 48,
+            tcf_policy_version: 2,
+            is_service_specific: true,
+            use_non_standard_stacks: false,
+            special_feature_opt_ins: [FeatureId(1)].iter().cloned().collect(),
+            purposes_consent: [1, 2, 4, 10].iter().cloned().map(PurposeId).collect(),
+            purposes_li_transparency: [2, 7].iter().cloned().map(PurposeId).collect(),
+            purpose_one_treatment: false,
+            publisher_cc: "DE".to_string(),
+            max_vendor_id: 755,
+            vendor_consent: [32, 755].iter().cloned().map(VendorId).collect(),
+            max_vendor_legitimate_interest_id: 0,
+            vendor_legitimate_interest: VendorSet::new(),
+            publisher_restrictions: vec![PublisherRestriction {
+                purpose_id: PurposeId(2),
+                restriction_type: RestrictionType::RequireConsent,
+                vendors: (10..21).map(VendorId).collect(),
+            }],
+            segments: Vec::new(),
+        });
+
+        let serialized = v.to_string().unwrap();
+        assert_eq!(serialized.parse::<VendorConsent>().unwrap(), v);
+    }
+
+    #[test]
+    fn serialize_v2_segments() {
+        let s = "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA.IFoEUQQgAIQwgIwQABAEAAAAOIAACAIAAAAQAIAgEAACEAAAAAgAQBAAAAAAAGBAAgAAAAAAAFAAECAAAgAAQARAEQAAAAAJAAIAAgAAAYQEAAAQmAgBC3ZAYzUw.YAAAAAAAAAAA";
+        let v: VendorConsent = s.parse().unwrap();
+        match v {
+            VendorConsent::V2(ref v2) => assert_eq!(v2.segments.len(), 2),
+            _ => panic!("expected v2"),
+        }
+        assert_eq!(v.to_string().unwrap(), s);
+
+        // segments in the standard alphabet are written back web-safe
+        let v: VendorConsent = "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA.P/+A=="
+            .parse()
+            .unwrap();
+        assert_eq!(
+            v.to_string().unwrap(),
+            "COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA.P_-A"
+        );
+
+        assert!("COvFyGBOvFyGBAbAAAENAPCAAOAAAAAAAAAAAEEUACCKAAA.!"
+            .parse::<VendorConsent>()
+            .is_err());
+        assert!("BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA.YAAAAAAAAAAA"
+            .parse::<VendorConsent>()
+            .is_err());
+    }
+
+    #[test]
+    fn validation_options() {
+        let s = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA";
+        let strict = ValidationOptions {
+            latest_timestamp: Some("2018-01-01T00:00:00Z".parse().unwrap()),
+            reject_last_updated_before_created: true,
+            ..Default::default()
+        };
+        let mut v = VendorConsent::parse_with(s, &strict).unwrap();
+
+        let before_created = ValidationOptions {
+            latest_timestamp: Some("2017-11-07T19:15:55.3Z".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(VendorConsent::parse_with(s, &before_created).is_err());
+
+        if let VendorConsent::V1(ref mut v1) = v {
+            v1.last_updated = "2017-11-07T19:15:55.3Z".parse().unwrap();
         }
+        assert!(v.validate(&strict).is_err());
+        assert!(v.validate(&ValidationOptions::default()).is_ok());
     }
 }
@@ -12,97 +12,1232 @@ use std::convert::From;
 use std::error;
 use std::fmt::{self, Display};
 use std::hash;
+use std::marker::PhantomData;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde;
-use serde::ser::SerializeSeq;
-use serde_json;
+use serde::de;
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde_json::{self, Map, Value};
 
-trait HasId<T> {
+use ids::{FeatureId, FeatureSet, Id, PurposeId, PurposeSet, StackId, VendorId, VendorSet};
+use timestamp::Deciseconds;
+use vendor_consent::{PublisherRestriction, RestrictionType, VendorConsent};
+
+pub(crate) trait HasId<T> {
     fn id(&self) -> T;
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Deserialized by hand: #[serde(flatten)] would buffer each vendor's
+// unmodeled fields before moving them into extra.
+#[derive(Serialize, Debug)]
 pub struct Vendor {
-    pub id: u16,
+    pub id: VendorId,
     pub name: String,
     #[serde(rename = "policyUrl")]
     pub policy_url: String,
+    // "purposes" in v2 vendor lists
     #[serde(rename = "purposeIds")]
-    pub purpose_ids: Vec<u8>,
+    pub purpose_ids: Vec<PurposeId>,
+    // "legIntPurposes" in v2 vendor lists
     #[serde(rename = "legIntPurposeIds")]
-    pub leg_int_purpose_ids: Vec<u8>,
+    pub leg_int_purpose_ids: Vec<PurposeId>,
+    // Purposes whose legal basis publisher restrictions may switch, absent
+    // from v1 vendor lists
+    #[serde(rename = "flexiblePurposes", skip_serializing_if = "Vec::is_empty")]
+    pub flexible_purpose_ids: Vec<PurposeId>,
+    // "features" in v2 vendor lists
     #[serde(rename = "featureIds")]
-    pub feature_ids: Vec<u8>,
+    pub feature_ids: Vec<FeatureId>,
+    // Purposes processed without consent, absent from v1 vendor lists
+    #[serde(rename = "specialPurposes", skip_serializing_if = "Vec::is_empty")]
+    pub special_purpose_ids: Vec<PurposeId>,
+    // Features the user must opt in to, absent from v1 vendor lists
+    #[serde(rename = "specialFeatures", skip_serializing_if = "Vec::is_empty")]
+    pub special_feature_ids: Vec<FeatureId>,
+    // Whether the vendor accesses devices other than with cookies, absent from
+    // v1 vendor lists
+    #[serde(rename = "usesNonCookieAccess", skip_serializing_if = "Option::is_none")]
+    pub uses_non_cookie_access: Option<bool>,
+    // When the vendor was deleted from the vendor list, if it has been
+    #[serde(rename = "deletedDate", skip_serializing_if = "Option::is_none")]
+    pub deleted_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow: Option<Overflow>,
+    // Fields this crate does not model, kept so re-serializing preserves them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Overflow {
+    // Largest HTTP GET request, in kilobytes, the vendor accepts
+    #[serde(rename = "httpGetLimit")]
+    pub http_get_limit: u16,
+}
+
+// Field names of a JSON object deserialized by hand, with any field not
+// listed kept as Other for the struct's unmodeled fields
+macro_rules! fields {
+    ($name:ident { $($($key:tt)|+ => $variant:ident,)* }) => {
+        enum $name {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct FieldVisitor;
+
+                impl<'de> de::Visitor<'de> for FieldVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                        formatter.write_str("a field name")
+                    }
+
+                    fn visit_str<E: de::Error>(self, field: &str) -> Result<$name, E> {
+                        Ok(match field {
+                            $($($key)|+ => $name::$variant,)*
+                            other => $name::Other(other.to_string()),
+                        })
+                    }
+                }
+
+                deserializer.deserialize_identifier(FieldVisitor)
+            }
+        }
+    };
 }
 
-impl HasId<u16> for Vendor {
-    fn id(&self) -> u16 {
+fields!(VendorField {
+    "id" => Id,
+    "name" => Name,
+    "policyUrl" => PolicyUrl,
+    "purposeIds" | "purposes" => Purposes,
+    "legIntPurposeIds" | "legIntPurposes" => LegIntPurposes,
+    "flexiblePurposes" => FlexiblePurposes,
+    "featureIds" | "features" => Features,
+    "specialPurposes" => SpecialPurposes,
+    "specialFeatures" => SpecialFeatures,
+    "usesNonCookieAccess" => UsesNonCookieAccess,
+    "deletedDate" => DeletedDate,
+    "overflow" => Overflow,
+});
+
+impl<'de> serde::Deserialize<'de> for Vendor {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct VendorVisitor;
+
+        impl<'de> de::Visitor<'de> for VendorVisitor {
+            type Value = Vendor;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a vendor")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Vendor, A::Error>
+            where A: de::MapAccess<'de>
+            {
+                let mut id = None;
+                let mut name = None;
+                let mut policy_url = None;
+                let mut purpose_ids = None;
+                let mut leg_int_purpose_ids = None;
+                let mut feature_ids = None;
+                let mut flexible_purpose_ids = Vec::new();
+                let mut special_purpose_ids = Vec::new();
+                let mut special_feature_ids = Vec::new();
+                let mut uses_non_cookie_access = None;
+                let mut deleted_date = None;
+                let mut overflow = None;
+                let mut extra = Map::new();
+
+                while let Some(field) = access.next_key()? {
+                    match field {
+                        VendorField::Id => id = Some(access.next_value()?),
+                        VendorField::Name => name = Some(access.next_value()?),
+                        VendorField::PolicyUrl => policy_url = Some(access.next_value()?),
+                        VendorField::Purposes => purpose_ids = Some(access.next_value()?),
+                        VendorField::LegIntPurposes => {
+                            leg_int_purpose_ids = Some(access.next_value()?)
+                        }
+                        VendorField::FlexiblePurposes => {
+                            flexible_purpose_ids = access.next_value()?
+                        }
+                        VendorField::Features => feature_ids = Some(access.next_value()?),
+                        VendorField::SpecialPurposes => special_purpose_ids = access.next_value()?,
+                        VendorField::SpecialFeatures => special_feature_ids = access.next_value()?,
+                        VendorField::UsesNonCookieAccess => {
+                            uses_non_cookie_access = access.next_value()?
+                        }
+                        VendorField::DeletedDate => deleted_date = access.next_value()?,
+                        VendorField::Overflow => overflow = access.next_value()?,
+                        VendorField::Other(key) => {
+                            extra.insert(key, access.next_value()?);
+                        }
+                    }
+                }
+
+                Ok(Vendor {
+                    id: id.ok_or_else(|| de::Error::missing_field("id"))?,
+                    name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+                    policy_url: policy_url.ok_or_else(|| de::Error::missing_field("policyUrl"))?,
+                    purpose_ids: purpose_ids.ok_or_else(|| de::Error::missing_field("purposeIds"))?,
+                    leg_int_purpose_ids: leg_int_purpose_ids
+                        .ok_or_else(|| de::Error::missing_field("legIntPurposeIds"))?,
+                    flexible_purpose_ids,
+                    feature_ids: feature_ids.ok_or_else(|| de::Error::missing_field("featureIds"))?,
+                    special_purpose_ids,
+                    special_feature_ids,
+                    uses_non_cookie_access,
+                    deleted_date,
+                    overflow,
+                    extra,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(VendorVisitor)
+    }
+}
+
+impl HasId<VendorId> for Vendor {
+    fn id(&self) -> VendorId {
         self.id
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegalBasis {
+    Consent,
+    LegitimateInterest,
+    NotAllowed,
+}
+
+impl Vendor {
+    // Whether the vendor had not yet been deleted from the vendor list at the
+    // given time. Consent given to a vendor does not apply after its deletion.
+    pub fn is_active_at(&self, now: Deciseconds) -> bool {
+        self.deleted_date.is_none_or(|deleted| now.to_datetime() < deleted)
+    }
+
+    // Legal basis the vendor may process data for the purpose under, once the
+    // publisher restrictions in a consent string are applied.
+    //
+    // Restrictions can forbid the purpose, or switch a flexible purpose from
+    // consent to legitimate interest or back. Purpose 1 can never rely on
+    // legitimate interest. Contradictory restrictions forbid the purpose.
+    pub fn legal_basis<P: Into<PurposeId>>(
+        &self,
+        purpose_id: P,
+        restrictions: &[PublisherRestriction],
+    ) -> LegalBasis {
+        let purpose_id = purpose_id.into();
+
+        let declared = if self.purpose_ids.contains(&purpose_id) {
+            LegalBasis::Consent
+        } else if self.leg_int_purpose_ids.contains(&purpose_id) {
+            LegalBasis::LegitimateInterest
+        } else {
+            return LegalBasis::NotAllowed;
+        };
+
+        let mut required: Option<LegalBasis> = None;
+        for r in restrictions
+            .iter()
+            .filter(|r| r.purpose_id == purpose_id && r.vendors.contains(self.id))
+        {
+            let basis = match r.restriction_type {
+                RestrictionType::NotAllowed => return LegalBasis::NotAllowed,
+                RestrictionType::RequireConsent => LegalBasis::Consent,
+                RestrictionType::RequireLegitimateInterest => LegalBasis::LegitimateInterest,
+            };
+            if required.is_some_and(|required| required != basis) {
+                return LegalBasis::NotAllowed;
+            }
+            required = Some(basis);
+        }
+
+        let basis = match required {
+            Some(basis) if basis != declared => {
+                if !self.flexible_purpose_ids.contains(&purpose_id) {
+                    return LegalBasis::NotAllowed;
+                }
+                basis
+            }
+            _ => declared,
+        };
+
+        if basis == LegalBasis::LegitimateInterest && purpose_id == PurposeId(1) {
+            return LegalBasis::NotAllowed;
+        }
+        basis
+    }
+}
+
+// Deserialized by hand like vendors, so no object in a vendor list
+// buffers its unmodeled fields.
+#[derive(Serialize, Debug)]
 pub struct Purpose {
-    pub id: u8,
+    pub id: PurposeId,
     pub name: String,
     pub description: String,
+    // Fields this crate does not model, kept so re-serializing preserves them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl HasId<u8> for Purpose {
-    fn id(&self) -> u8 {
+impl HasId<PurposeId> for Purpose {
+    fn id(&self) -> PurposeId {
         self.id
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl<'de> serde::Deserialize<'de> for Purpose {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (id, name, description, extra) = deserializer.deserialize_map(DescribedVisitor {
+            expecting: "a purpose",
+            marker: PhantomData,
+        })?;
+        Ok(Purpose {
+            id,
+            name,
+            description,
+            extra,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
 pub struct Feature {
-    pub id: u8,
+    pub id: FeatureId,
+    pub name: String,
+    pub description: String,
+    // Fields this crate does not model, kept so re-serializing preserves them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl HasId<FeatureId> for Feature {
+    fn id(&self) -> FeatureId {
+        self.id
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Feature {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (id, name, description, extra) = deserializer.deserialize_map(DescribedVisitor {
+            expecting: "a feature",
+            marker: PhantomData,
+        })?;
+        Ok(Feature {
+            id,
+            name,
+            description,
+            extra,
+        })
+    }
+}
+
+fields!(DescribedField {
+    "id" => Id,
+    "name" => Name,
+    "description" => Description,
+});
+
+// Visits a purpose or feature: its ID, name, description and unmodeled fields
+struct DescribedVisitor<I> {
+    expecting: &'static str,
+    marker: PhantomData<I>,
+}
+
+impl<'de, I: serde::Deserialize<'de>> de::Visitor<'de> for DescribedVisitor<I> {
+    type Value = (I, String, String, Map<String, Value>);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.expecting)
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where A: de::MapAccess<'de>
+    {
+        let mut id = None;
+        let mut name = None;
+        let mut description = None;
+        let mut extra = Map::new();
+
+        while let Some(field) = access.next_key()? {
+            match field {
+                DescribedField::Id => id = Some(access.next_value()?),
+                DescribedField::Name => name = Some(access.next_value()?),
+                DescribedField::Description => description = Some(access.next_value()?),
+                DescribedField::Other(key) => {
+                    extra.insert(key, access.next_value()?);
+                }
+            }
+        }
+
+        Ok((
+            id.ok_or_else(|| de::Error::missing_field("id"))?,
+            name.ok_or_else(|| de::Error::missing_field("name"))?,
+            description.ok_or_else(|| de::Error::missing_field("description"))?,
+            extra,
+        ))
+    }
+}
+
+// Purposes and special features a CMP may present as a single choice
+#[derive(Serialize, Debug)]
+pub struct Stack {
+    pub id: StackId,
+    #[serde(rename = "purposes")]
+    pub purpose_ids: Vec<PurposeId>,
+    #[serde(rename = "specialFeatures")]
+    pub special_feature_ids: Vec<FeatureId>,
     pub name: String,
     pub description: String,
+    // Fields this crate does not model, kept so re-serializing preserves them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-impl HasId<u8> for Feature {
-    fn id(&self) -> u8 {
+impl HasId<StackId> for Stack {
+    fn id(&self) -> StackId {
         self.id
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+fields!(StackField {
+    "id" => Id,
+    "purposes" => Purposes,
+    "specialFeatures" => SpecialFeatures,
+    "name" => Name,
+    "description" => Description,
+});
+
+impl<'de> serde::Deserialize<'de> for Stack {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StackVisitor;
+
+        impl<'de> de::Visitor<'de> for StackVisitor {
+            type Value = Stack;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a stack")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Stack, A::Error>
+            where A: de::MapAccess<'de>
+            {
+                let mut id = None;
+                let mut purpose_ids = None;
+                let mut special_feature_ids = None;
+                let mut name = None;
+                let mut description = None;
+                let mut extra = Map::new();
+
+                while let Some(field) = access.next_key()? {
+                    match field {
+                        StackField::Id => id = Some(access.next_value()?),
+                        StackField::Purposes => purpose_ids = Some(access.next_value()?),
+                        StackField::SpecialFeatures => {
+                            special_feature_ids = Some(access.next_value()?)
+                        }
+                        StackField::Name => name = Some(access.next_value()?),
+                        StackField::Description => description = Some(access.next_value()?),
+                        StackField::Other(key) => {
+                            extra.insert(key, access.next_value()?);
+                        }
+                    }
+                }
+
+                Ok(Stack {
+                    id: id.ok_or_else(|| de::Error::missing_field("id"))?,
+                    purpose_ids: purpose_ids.ok_or_else(|| de::Error::missing_field("purposes"))?,
+                    special_feature_ids: special_feature_ids
+                        .ok_or_else(|| de::Error::missing_field("specialFeatures"))?,
+                    name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+                    description: description
+                        .ok_or_else(|| de::Error::missing_field("description"))?,
+                    extra,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(StackVisitor)
+    }
+}
+
+impl Stack {
+    // Whether every purpose and special feature in the stack is in the given sets
+    pub fn is_covered_by(&self, purposes: &PurposeSet, special_features: &FeatureSet) -> bool {
+        self.purpose_ids.iter().all(|id| purposes.contains(*id))
+            && self
+                .special_feature_ids
+                .iter()
+                .all(|id| special_features.contains(*id))
+    }
+
+    // Purposes in the low 32 bits and special features in the high 32 bits,
+    // None if an ID does not fit
+    fn mask(&self) -> Option<u64> {
+        let purposes = self.purpose_ids.iter().map(|id| (id.0, 0));
+        let special_features = self.special_feature_ids.iter().map(|id| (id.0, 32));
+        purposes
+            .chain(special_features)
+            .try_fold(0, |mask, (id, offset)| match id {
+                1..=32 => Some(mask | 1 << (offset + id - 1)),
+                _ => None,
+            })
+    }
+}
+
+// Granted purposes and special features, using as few stacks as possible
+#[derive(Serialize, Debug, PartialEq)]
+pub struct StackGrouping {
+    pub stacks: Vec<StackId>,
+
+    // Granted purposes not in any of the stacks
+    pub purposes: Vec<PurposeId>,
+
+    // Granted special features not in any of the stacks
+    #[serde(rename = "specialFeatures")]
+    pub special_features: Vec<FeatureId>,
+}
+
+// Serialized in the field order and format of the IAB's published JSON for
+// the vendor list's version. Deserialized by hand like vendors.
+#[derive(Debug)]
 pub struct VendorList {
-    #[serde(rename = "vendorListVersion")]
+    // Version of the GVL specification, absent for v1 vendor lists
+    pub gvl_specification_version: Option<u8>,
+
+    // "vendorListVersion"
     pub version: u16,
 
-    #[serde(rename = "lastUpdated")]
+    // Version of the TCF policy, absent for v1 vendor lists
+    pub tcf_policy_version: Option<u8>,
+
     pub last_updated: DateTime<Utc>,
 
+    pub purposes: HashMap<PurposeId, Purpose>,
+
+    pub features: HashMap<FeatureId, Feature>,
+
+    // Absent from v1 vendor lists
+    pub special_purposes: HashMap<PurposeId, Purpose>,
+
+    // Absent from v1 vendor lists
+    pub special_features: HashMap<FeatureId, Feature>,
+
+    // Call reindex() after modifying, so the vendor queries see the changes
+    pub vendors: HashMap<VendorId, Vendor>,
+
+    // Absent from v1 vendor lists
+    pub stacks: HashMap<StackId, Stack>,
+
+    // Translated purposes, features and stacks, keyed by two-letter ISO639-1
+    // language code. Not part of the vendor list JSON.
+    pub translations: HashMap<String, Translations>,
+
+    // Fields this crate does not model, kept so re-serializing preserves them
+    pub extra: Map<String, Value>,
+
+    // Built by the first vendor query rather than on load: building it adds
+    // about 15% to parsing a full vendor list, and most users only check
+    // consent against the list. Reset by reindex().
+    pub(crate) index: OnceLock<VendorIndex>,
+}
+
+fields!(VendorListField {
+    "gvlSpecificationVersion" => GvlSpecificationVersion,
+    "vendorListVersion" => Version,
+    "tcfPolicyVersion" => TcfPolicyVersion,
+    "lastUpdated" => LastUpdated,
+    "purposes" => Purposes,
+    "features" => Features,
+    "specialPurposes" => SpecialPurposes,
+    "specialFeatures" => SpecialFeatures,
+    "vendors" => Vendors,
+    "stacks" => Stacks,
+});
+
+// ID-keyed map deserialized from either a list (v1) or an object (v2)
+struct IdMap<K, V>(HashMap<K, V>);
+
+impl<'de, K, V> serde::Deserialize<'de> for IdMap<K, V>
+where
+    K: Eq + hash::Hash,
+    V: HasId<K> + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_list_as_id_map(deserializer).map(IdMap)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for VendorList {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct VendorListVisitor;
+
+        impl<'de> de::Visitor<'de> for VendorListVisitor {
+            type Value = VendorList;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a vendor list")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<VendorList, A::Error>
+            where A: de::MapAccess<'de>
+            {
+                let mut gvl_specification_version = None;
+                let mut version = None;
+                let mut tcf_policy_version = None;
+                let mut last_updated = None;
+                let mut purposes = None;
+                let mut features = None;
+                let mut special_purposes = IdMap(HashMap::new());
+                let mut special_features = IdMap(HashMap::new());
+                let mut vendors = None;
+                let mut stacks = IdMap(HashMap::new());
+                let mut extra = Map::new();
+
+                while let Some(field) = access.next_key()? {
+                    match field {
+                        VendorListField::GvlSpecificationVersion => {
+                            gvl_specification_version = access.next_value()?
+                        }
+                        VendorListField::Version => version = Some(access.next_value()?),
+                        VendorListField::TcfPolicyVersion => {
+                            tcf_policy_version = access.next_value()?
+                        }
+                        VendorListField::LastUpdated => last_updated = Some(access.next_value()?),
+                        VendorListField::Purposes => purposes = Some(access.next_value()?),
+                        VendorListField::Features => features = Some(access.next_value()?),
+                        VendorListField::SpecialPurposes => special_purposes = access.next_value()?,
+                        VendorListField::SpecialFeatures => special_features = access.next_value()?,
+                        VendorListField::Vendors => vendors = Some(access.next_value()?),
+                        VendorListField::Stacks => stacks = access.next_value()?,
+                        VendorListField::Other(key) => {
+                            extra.insert(key, access.next_value()?);
+                        }
+                    }
+                }
+
+                let purposes: IdMap<_, _> =
+                    purposes.ok_or_else(|| de::Error::missing_field("purposes"))?;
+                let features: IdMap<_, _> =
+                    features.ok_or_else(|| de::Error::missing_field("features"))?;
+                let vendors: IdMap<_, _> =
+                    vendors.ok_or_else(|| de::Error::missing_field("vendors"))?;
+
+                Ok(VendorList {
+                    gvl_specification_version,
+                    version: version.ok_or_else(|| de::Error::missing_field("vendorListVersion"))?,
+                    tcf_policy_version,
+                    last_updated: last_updated
+                        .ok_or_else(|| de::Error::missing_field("lastUpdated"))?,
+                    purposes: purposes.0,
+                    features: features.0,
+                    special_purposes: special_purposes.0,
+                    special_features: special_features.0,
+                    vendors: vendors.0,
+                    stacks: stacks.0,
+                    translations: HashMap::new(),
+                    extra,
+                    index: OnceLock::new(),
+                })
+            }
+        }
+
+        deserializer.deserialize_map(VendorListVisitor)
+    }
+}
+
+// Inverted indexes from purposes, features and names to the vendors declaring
+// them. Purposes and features are indexed by ID - 1.
+#[derive(Debug, Default)]
+pub(crate) struct VendorIndex {
+    purposes: Vec<VendorSet>,
+    leg_int_purposes: Vec<VendorSet>,
+    features: Vec<VendorSet>,
+    special_features: Vec<VendorSet>,
+
+    // (lowercase name, ID), sorted
+    names: Vec<(String, VendorId)>,
+}
+
+impl VendorIndex {
+    fn new(vendors: &HashMap<VendorId, Vendor>) -> VendorIndex {
+        fn add<K: Id>(index: &mut Vec<VendorSet>, keys: &[K], id: VendorId) {
+            for i in keys.iter().filter_map(|key| key.index()) {
+                if i >= index.len() {
+                    index.resize_with(i + 1, VendorSet::new);
+                }
+                index[i].insert(id);
+            }
+        }
+
+        let mut index = VendorIndex::default();
+        for vendor in vendors.values() {
+            add(&mut index.purposes, &vendor.purpose_ids, vendor.id);
+            add(&mut index.leg_int_purposes, &vendor.leg_int_purpose_ids, vendor.id);
+            add(&mut index.features, &vendor.feature_ids, vendor.id);
+            add(&mut index.special_features, &vendor.special_feature_ids, vendor.id);
+            index.names.push((vendor.name.to_lowercase(), vendor.id));
+        }
+        index.names.sort_unstable();
+        index
+    }
+}
+
+impl VendorList {
+    // Whether this is a TCF v2 (or later) global vendor list.
+    pub fn is_v2(&self) -> bool {
+        self.gvl_specification_version.is_some_and(|v| v >= 2)
+    }
+
+    pub fn to_string(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(From::from)
+    }
+
+    // Vendors that had not been deleted at the given time, ordered by ID.
+    pub fn active_vendors_at(&self, now: Deciseconds) -> Vec<&Vendor> {
+        let mut vendors: Vec<&Vendor> =
+            self.vendors.values().filter(|v| v.is_active_at(now)).collect();
+        vendors.sort_by_key(|v| v.id);
+        vendors
+    }
+
+    // Rebuilds the lookup indexes after vendors have been modified.
+    pub fn reindex(&mut self) {
+        self.index = OnceLock::new();
+    }
+
+    fn index(&self) -> &VendorIndex {
+        self.index.get_or_init(|| VendorIndex::new(&self.vendors))
+    }
+
+    // Vendors declaring the purpose under consent, ordered by ID.
+    pub fn vendors_with_purpose<I: Into<PurposeId>>(&self, id: I) -> Vec<&Vendor> {
+        self.lookup(&self.index().purposes, id.into())
+    }
+
+    // Vendors declaring the purpose under legitimate interest, ordered by ID.
+    pub fn vendors_with_leg_int_purpose<I: Into<PurposeId>>(&self, id: I) -> Vec<&Vendor> {
+        self.lookup(&self.index().leg_int_purposes, id.into())
+    }
+
+    // Vendors using the feature, ordered by ID.
+    pub fn vendors_with_feature<I: Into<FeatureId>>(&self, id: I) -> Vec<&Vendor> {
+        self.lookup(&self.index().features, id.into())
+    }
+
+    // Vendors using the special feature, ordered by ID.
+    pub fn vendors_with_special_feature<I: Into<FeatureId>>(&self, id: I) -> Vec<&Vendor> {
+        self.lookup(&self.index().special_features, id.into())
+    }
+
+    // Vendors whose name starts with the prefix, ignoring case, ordered by name.
+    pub fn vendors_by_name_prefix(&self, prefix: &str) -> Vec<&Vendor> {
+        let prefix = prefix.to_lowercase();
+        let names = &self.index().names;
+        let start = names.partition_point(|(name, _)| name.as_str() < prefix.as_str());
+        names[start..]
+            .iter()
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .filter_map(|(_, id)| self.vendors.get(id))
+            .collect()
+    }
+
+    fn lookup<K: Id>(&self, index: &[VendorSet], key: K) -> Vec<&Vendor> {
+        key.index()
+            .and_then(|i| index.get(i))
+            .into_iter()
+            .flat_map(|ids| ids.iter())
+            .filter_map(|id| self.vendors.get(&id))
+            .collect()
+    }
+
+    // Merges a purposes-{lang}.json translation file onto the vendor list.
+    pub fn add_translations(
+        &mut self,
+        language: &str,
+        translations: Translations,
+    ) -> Result<(), Error> {
+        check_translated_ids("purpose", &translations.purposes, &self.purposes, language)?;
+        check_translated_ids("feature", &translations.features, &self.features, language)?;
+        check_translated_ids(
+            "special purpose",
+            &translations.special_purposes,
+            &self.special_purposes,
+            language,
+        )?;
+        check_translated_ids(
+            "special feature",
+            &translations.special_features,
+            &self.special_features,
+            language,
+        )?;
+        check_translated_ids("stack", &translations.stacks, &self.stacks, language)?;
+
+        self.translations.insert(language.to_lowercase(), translations);
+        Ok(())
+    }
+
+    // Name of the purpose in the given language, falling back to English.
+    pub fn purpose_name<I: Into<PurposeId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.purposes.get(&id).map(|p| &p.name))
+            .or_else(|| self.purposes.get(&id).map(|p| &p.name[..]))
+    }
+
+    // Description of the purpose in the given language, falling back to English.
+    pub fn purpose_description<I: Into<PurposeId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.purposes.get(&id).map(|p| &p.description))
+            .or_else(|| self.purposes.get(&id).map(|p| &p.description[..]))
+    }
+
+    // Name of the feature in the given language, falling back to English.
+    pub fn feature_name<I: Into<FeatureId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.features.get(&id).map(|f| &f.name))
+            .or_else(|| self.features.get(&id).map(|f| &f.name[..]))
+    }
+
+    // Description of the feature in the given language, falling back to English.
+    pub fn feature_description<I: Into<FeatureId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.features.get(&id).map(|f| &f.description))
+            .or_else(|| self.features.get(&id).map(|f| &f.description[..]))
+    }
+
+    // Name of the special purpose in the given language, falling back to English.
+    pub fn special_purpose_name<I: Into<PurposeId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.special_purposes.get(&id).map(|p| &p.name))
+            .or_else(|| self.special_purposes.get(&id).map(|p| &p.name[..]))
+    }
+
+    // Description of the special purpose in the given language, falling back
+    // to English.
+    pub fn special_purpose_description<I: Into<PurposeId>>(
+        &self,
+        id: I,
+        language: &str,
+    ) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.special_purposes.get(&id).map(|p| &p.description))
+            .or_else(|| self.special_purposes.get(&id).map(|p| &p.description[..]))
+    }
+
+    // Name of the special feature in the given language, falling back to English.
+    pub fn special_feature_name<I: Into<FeatureId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.special_features.get(&id).map(|f| &f.name))
+            .or_else(|| self.special_features.get(&id).map(|f| &f.name[..]))
+    }
+
+    // Description of the special feature in the given language, falling back
+    // to English.
+    pub fn special_feature_description<I: Into<FeatureId>>(
+        &self,
+        id: I,
+        language: &str,
+    ) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.special_features.get(&id).map(|f| &f.description))
+            .or_else(|| self.special_features.get(&id).map(|f| &f.description[..]))
+    }
+
+    // Name of the stack in the given language, falling back to English.
+    pub fn stack_name<I: Into<StackId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.stacks.get(&id).map(|s| &s.name))
+            .or_else(|| self.stacks.get(&id).map(|s| &s.name[..]))
+    }
+
+    // Description of the stack in the given language, falling back to English.
+    pub fn stack_description<I: Into<StackId>>(&self, id: I, language: &str) -> Option<&str> {
+        let id = id.into();
+        self.translation(language, |t| t.stacks.get(&id).map(|s| &s.description))
+            .or_else(|| self.stacks.get(&id).map(|s| &s.description[..]))
+    }
+
+    // Stacks whose purposes and special features are all in the given sets,
+    // ordered by ID.
+    pub fn stacks_covered_by(
+        &self,
+        purposes: &PurposeSet,
+        special_features: &FeatureSet,
+    ) -> Vec<&Stack> {
+        let mut stacks: Vec<&Stack> = self
+            .stacks
+            .values()
+            .filter(|s| s.is_covered_by(purposes, special_features))
+            .collect();
+        stacks.sort_by_key(|s| s.id);
+        stacks
+    }
+
+    // Groups the granted purposes and special features into the fewest stacks
+    // that cover as many of them as stacks can, without including anything
+    // that was not granted.
+    pub fn group_by_stacks(
+        &self,
+        purposes: &PurposeSet,
+        special_features: &FeatureSet,
+    ) -> StackGrouping {
+        let stacks: Vec<(StackId, u64)> = self
+            .stacks_covered_by(purposes, special_features)
+            .into_iter()
+            .filter_map(|s| s.mask().map(|mask| (s.id, mask)))
+            .filter(|&(_, mask)| mask != 0)
+            .collect();
+        let target = stacks.iter().fold(0, |acc, &(_, mask)| acc | mask);
+
+        // Breadth-first over unions of stacks, so the target is first reached
+        // with the fewest stacks. Each union remembers the union and stack it
+        // was reached from.
+        let mut from: HashMap<u64, (u64, StackId)> = HashMap::new();
+        let mut frontier = vec![0];
+        while target != 0 && !from.contains_key(&target) {
+            let mut next = Vec::new();
+            for &union in &frontier {
+                for &(id, mask) in &stacks {
+                    let next_union = union | mask;
+                    if next_union != union && !from.contains_key(&next_union) {
+                        from.insert(next_union, (union, id));
+                        next.push(next_union);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let mut grouping = StackGrouping {
+            stacks: Vec::new(),
+            purposes: Vec::new(),
+            special_features: Vec::new(),
+        };
+
+        let mut union = target;
+        while union != 0 {
+            let (prev, id) = from[&union];
+            grouping.stacks.push(id);
+            union = prev;
+        }
+        grouping.stacks.sort();
+
+        let covered =
+            |id: u8, offset: u8| (1..=32).contains(&id) && target & 1 << (offset + id - 1) != 0;
+        grouping.purposes = purposes.iter().filter(|id| !covered(id.0, 0)).collect();
+        grouping.special_features = special_features
+            .iter()
+            .filter(|id| !covered(id.0, 32))
+            .collect();
+
+        grouping
+    }
+
+    // Groups the purposes consented to (and for TCF v2 consent strings, the
+    // special features opted in to) into stacks.
+    pub fn group_consent_by_stacks(&self, consent: &VendorConsent) -> StackGrouping {
+        match consent {
+            VendorConsent::V1(ref v1) => {
+                self.group_by_stacks(&v1.purposes_allowed, &FeatureSet::new())
+            }
+            VendorConsent::V2(ref v2) => {
+                self.group_by_stacks(&v2.purposes_consent, &v2.special_feature_opt_ins)
+            }
+        }
+    }
+
+    fn translation<'a, F>(&'a self, language: &str, f: F) -> Option<&'a str>
+    where F: Fn(&'a Translations) -> Option<&'a String>
+    {
+        self.translations
+            .get(&language.to_lowercase())
+            .and_then(f)
+            .map(|s| &s[..])
+    }
+}
+
+impl FromStr for VendorList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(From::from)
+    }
+}
+
+impl serde::Serialize for VendorList {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let v2 = self.is_v2();
+        let json = VendorListJson {
+            gvl_specification_version: self.gvl_specification_version,
+            version: self.version,
+            tcf_policy_version: self.tcf_policy_version,
+            last_updated: timestamp(&self.last_updated),
+            purposes: Entries::new(&self.purposes, v2),
+            special_purposes: Entries::v2_only(&self.special_purposes, v2),
+            features: Entries::new(&self.features, v2),
+            special_features: Entries::v2_only(&self.special_features, v2),
+            stacks: Entries::v2_only(&self.stacks, v2),
+            vendors: Entries::new(&self.vendors, v2),
+            extra: &self.extra,
+        };
+        serde::Serialize::serialize(&json, serializer)
+    }
+}
+
+// Field names and order of the IAB's published vendor lists. Fields absent
+// from v1 lists are always present in v2 lists.
+#[derive(Serialize)]
+struct VendorListJson<'a> {
+    #[serde(
+        rename = "gvlSpecificationVersion",
+        skip_serializing_if = "Option::is_none"
+    )]
+    gvl_specification_version: Option<u8>,
+    #[serde(rename = "vendorListVersion")]
+    version: u16,
+    #[serde(rename = "tcfPolicyVersion", skip_serializing_if = "Option::is_none")]
+    tcf_policy_version: Option<u8>,
+    #[serde(rename = "lastUpdated")]
+    last_updated: String,
+    purposes: Entries<'a, PurposeId, Purpose>,
+    #[serde(rename = "specialPurposes", skip_serializing_if = "Option::is_none")]
+    special_purposes: Option<Entries<'a, PurposeId, Purpose>>,
+    features: Entries<'a, FeatureId, Feature>,
+    #[serde(rename = "specialFeatures", skip_serializing_if = "Option::is_none")]
+    special_features: Option<Entries<'a, FeatureId, Feature>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stacks: Option<Entries<'a, StackId, Stack>>,
+    vendors: Entries<'a, VendorId, Vendor>,
+    #[serde(flatten)]
+    extra: &'a Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct VendorV1Json<'a> {
+    id: VendorId,
+    name: &'a str,
+    #[serde(rename = "policyUrl")]
+    policy_url: &'a str,
+    #[serde(rename = "purposeIds")]
+    purpose_ids: &'a [PurposeId],
+    #[serde(rename = "legIntPurposeIds")]
+    leg_int_purpose_ids: &'a [PurposeId],
+    #[serde(rename = "featureIds")]
+    feature_ids: &'a [FeatureId],
+    #[serde(rename = "deletedDate", skip_serializing_if = "Option::is_none")]
+    deleted_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overflow: Option<&'a Overflow>,
+    #[serde(flatten)]
+    extra: &'a Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct VendorV2Json<'a> {
+    id: VendorId,
+    name: &'a str,
+    purposes: &'a [PurposeId],
+    #[serde(rename = "legIntPurposes")]
+    leg_int_purposes: &'a [PurposeId],
+    #[serde(rename = "flexiblePurposes")]
+    flexible_purposes: &'a [PurposeId],
+    #[serde(rename = "specialPurposes")]
+    special_purposes: &'a [PurposeId],
+    features: &'a [FeatureId],
+    #[serde(rename = "specialFeatures")]
+    special_features: &'a [FeatureId],
+    #[serde(rename = "policyUrl")]
+    policy_url: &'a str,
+    #[serde(rename = "usesNonCookieAccess", skip_serializing_if = "Option::is_none")]
+    uses_non_cookie_access: Option<bool>,
+    #[serde(rename = "deletedDate", skip_serializing_if = "Option::is_none")]
+    deleted_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overflow: Option<&'a Overflow>,
+    #[serde(flatten)]
+    extra: &'a Map<String, Value>,
+}
+
+// As the IAB formats them, e.g. "2020-07-02T16:00:23Z"
+fn timestamp(t: &DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+// Entries serialized differently in v1 and v2 vendor lists
+trait SerializeVersioned {
+    fn serialize_versioned<S: serde::Serializer>(
+        &self,
+        v2: bool,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>;
+}
+
+impl SerializeVersioned for Vendor {
+    fn serialize_versioned<S: serde::Serializer>(
+        &self,
+        v2: bool,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if !v2 {
+            let json = VendorV1Json {
+                id: self.id,
+                name: &self.name,
+                policy_url: &self.policy_url,
+                purpose_ids: &self.purpose_ids,
+                leg_int_purpose_ids: &self.leg_int_purpose_ids,
+                feature_ids: &self.feature_ids,
+                deleted_date: self.deleted_date.as_ref().map(timestamp),
+                overflow: self.overflow.as_ref(),
+                extra: &self.extra,
+            };
+            return serde::Serialize::serialize(&json, serializer);
+        }
+
+        let json = VendorV2Json {
+            id: self.id,
+            name: &self.name,
+            purposes: &self.purpose_ids,
+            leg_int_purposes: &self.leg_int_purpose_ids,
+            flexible_purposes: &self.flexible_purpose_ids,
+            special_purposes: &self.special_purpose_ids,
+            features: &self.feature_ids,
+            special_features: &self.special_feature_ids,
+            policy_url: &self.policy_url,
+            uses_non_cookie_access: self.uses_non_cookie_access,
+            deleted_date: self.deleted_date.as_ref().map(timestamp),
+            overflow: self.overflow.as_ref(),
+            extra: &self.extra,
+        };
+        serde::Serialize::serialize(&json, serializer)
+    }
+}
+
+macro_rules! serialize_unversioned {
+    ($($t:ty),*) => {
+        $(
+            impl SerializeVersioned for $t {
+                fn serialize_versioned<S: serde::Serializer>(
+                    &self,
+                    _v2: bool,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    serde::Serialize::serialize(self, serializer)
+                }
+            }
+        )*
+    };
+}
+
+serialize_unversioned!(Purpose, Feature, Stack);
+
+// An ID-keyed map in ID order, as a list in v1 vendor lists and an object
+// keyed by ID in v2 vendor lists
+struct Entries<'a, K: 'a, V: 'a> {
+    map: &'a HashMap<K, V>,
+    v2: bool,
+}
+
+impl<'a, K, V> Entries<'a, K, V> {
+    fn new(map: &'a HashMap<K, V>, v2: bool) -> Entries<'a, K, V> {
+        Entries { map, v2 }
+    }
+
+    // For entries absent from v1 vendor lists
+    fn v2_only(map: &'a HashMap<K, V>, v2: bool) -> Option<Entries<'a, K, V>> {
+        if v2 {
+            Some(Entries::new(map, v2))
+        } else {
+            None
+        }
+    }
+}
+
+struct Versioned<'a, V: 'a>(&'a V, bool);
+
+impl<'a, V: SerializeVersioned> serde::Serialize for Versioned<'a, V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize_versioned(self.1, serializer)
+    }
+}
+
+impl<'a, K, V> serde::Serialize for Entries<'a, K, V>
+where
+    K: Ord + Display,
+    V: HasId<K> + SerializeVersioned,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut values: Vec<&V> = self.map.values().collect();
+        values.sort_by_key(|v| v.id());
+
+        if self.v2 {
+            let mut map = serializer.serialize_map(Some(values.len()))?;
+            for v in values {
+                map.serialize_entry(&v.id().to_string(), &Versioned(v, true))?;
+            }
+            map.end()
+        } else {
+            let mut seq = serializer.serialize_seq(Some(values.len()))?;
+            for v in values {
+                seq.serialize_element(&Versioned(v, false))?;
+            }
+            seq.end()
+        }
+    }
+}
+
+// Translated purposes, features and stacks from a purposes-{lang}.json file.
+// Only the names and descriptions are used.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Translations {
     #[serde(
+        default,
         serialize_with = "serialize_id_map_as_list",
         deserialize_with = "deserialize_list_as_id_map"
     )]
-    pub purposes: HashMap<u8, Purpose>,
+    pub purposes: HashMap<PurposeId, Purpose>,
 
     #[serde(
+        default,
         serialize_with = "serialize_id_map_as_list",
         deserialize_with = "deserialize_list_as_id_map"
     )]
-    pub features: HashMap<u8, Feature>,
+    pub features: HashMap<FeatureId, Feature>,
 
     #[serde(
+        rename = "specialPurposes",
+        default,
         serialize_with = "serialize_id_map_as_list",
         deserialize_with = "deserialize_list_as_id_map"
     )]
-    pub vendors: HashMap<u16, Vendor>,
-}
+    pub special_purposes: HashMap<PurposeId, Purpose>,
 
-impl VendorList {
-    pub fn to_string(&self) -> Result<String, Error> {
-        serde_json::to_string(self).map_err(From::from)
-    }
+    #[serde(
+        rename = "specialFeatures",
+        default,
+        serialize_with = "serialize_id_map_as_list",
+        deserialize_with = "deserialize_list_as_id_map"
+    )]
+    pub special_features: HashMap<FeatureId, Feature>,
+
+    #[serde(
+        default,
+        serialize_with = "serialize_id_map_as_list",
+        deserialize_with = "deserialize_list_as_id_map"
+    )]
+    pub stacks: HashMap<StackId, Stack>,
 }
 
-impl FromStr for VendorList {
+impl FromStr for Translations {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -117,14 +1252,7 @@ pub enum Error {
 }
 
 impl error::Error for Error {
-    fn description(&self) -> &str {
-        match self {
-            Error::JsonError(ref err) => err.description(),
-            Error::Other(msg) => msg,
-        }
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::JsonError(ref err) => Some(err),
             Error::Other(_) => None,
@@ -147,23 +1275,86 @@ impl From<serde_json::Error> for Error {
     }
 }
 
-fn deserialize_list_as_id_map<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+struct IdMapVisitor<K, V> {
+    marker: PhantomData<(K, V)>,
+}
+
+impl<'de, K, V> de::Visitor<'de> for IdMapVisitor<K, V>
+where
+    K: Eq + hash::Hash,
+    V: HasId<K> + serde::Deserialize<'de>,
+{
+    type Value = HashMap<K, V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list or an ID-keyed map")
+    }
+
+    // v1 vendor lists
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where A: de::SeqAccess<'de>
+    {
+        let mut map = HashMap::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(v) = seq.next_element::<V>()? {
+            map.insert(v.id(), v);
+        }
+        Ok(map)
+    }
+
+    // v2 vendor lists
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where A: de::MapAccess<'de>
+    {
+        let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some((_, v)) = access.next_entry::<de::IgnoredAny, V>()? {
+            map.insert(v.id(), v);
+        }
+        Ok(map)
+    }
+}
+
+// Fails if the translations have an ID the vendor list does not.
+fn check_translated_ids<K, V, W>(
+    kind: &str,
+    translated: &HashMap<K, V>,
+    original: &HashMap<K, W>,
+    language: &str,
+) -> Result<(), Error>
+where
+    K: Eq + hash::Hash + Display,
+{
+    match translated.keys().find(|id| !original.contains_key(*id)) {
+        Some(id) => Err(Error::Other(format!(
+            "Unknown {} ID {} in '{}' translations",
+            kind, id, language
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn deserialize_list_as_id_map<'de, D, K, V>(
+    deserializer: D,
+) -> Result<HashMap<K, V>, D::Error>
 where
     D: serde::Deserializer<'de>,
     K: Eq + hash::Hash,
     V: HasId<K> + serde::Deserialize<'de>,
 {
-    let values: Vec<V> = serde::Deserialize::deserialize(deserializer)?;
-    Ok(values.into_iter().map(|v| (v.id(), v)).collect())
+    deserializer.deserialize_any(IdMapVisitor {
+        marker: PhantomData,
+    })
 }
 
-fn serialize_id_map_as_list<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_id_map_as_list<S, K, V>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
     K: Eq + hash::Hash + cmp::Ord,
     V: HasId<K> + serde::Serialize,
 {
-    let mut values: Vec<&V> = map.iter().map(|(_, v)| v).collect();
+    let mut values: Vec<&V> = map.values().collect();
     values.sort_by_key(|v| v.id());
 
     let mut seq = serializer.serialize_seq(Some(values.len()))?;
@@ -175,9 +1366,457 @@ where
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const VENDOR_LIST: &str = r#"{
+        "vendorListVersion": 8,
+        "lastUpdated": "2018-05-28T16:00:15Z",
+        "purposes": [
+            { "id": 1, "name": "Information storage and access", "description": "Storage" },
+            { "id": 2, "name": "Personalisation", "description": "Personalisation" }
+        ],
+        "features": [
+            { "id": 1, "name": "Offline data matching", "description": "Matching" }
+        ],
+        "vendors": [
+            {
+                "id": 32,
+                "name": "AppNexus Inc.",
+                "policyUrl": "https://www.appnexus.com/en/company/platform-privacy-policy",
+                "purposeIds": [1],
+                "legIntPurposeIds": [3],
+                "featureIds": [2, 3]
+            }
+        ]
+    }"#;
+
     #[test]
-    fn serialize_good() {}
+    fn serialize_good() {
+        let v1 = concat!(
+            r#"{"vendorListVersion":8,"lastUpdated":"2018-05-28T16:00:15Z","#,
+            r#""purposes":[{"id":1,"name":"Information storage and access","description":"Storage"}],"#,
+            r#""features":[{"id":1,"name":"Offline data matching","description":"Matching"}],"#,
+            r#""vendors":[{"id":8,"name":"Emerse Sverige AB","policyUrl":"https://www.emerse.com/privacy-policy/","#,
+            r#""purposeIds":[1,2],"legIntPurposeIds":[3],"featureIds":[],"deletedDate":"2018-06-01T00:00:00Z"},"#,
+            r#"{"id":32,"name":"AppNexus Inc.","policyUrl":"https://www.appnexus.com/en/company/platform-privacy-policy","#,
+            r#""purposeIds":[1],"legIntPurposeIds":[3],"featureIds":[2,3]}]}"#
+        );
+        assert_eq!(v1.parse::<VendorList>().unwrap().to_string().unwrap(), v1);
+
+        let v2 = concat!(
+            r#"{"gvlSpecificationVersion":2,"vendorListVersion":48,"tcfPolicyVersion":2,"lastUpdated":"2020-07-02T16:00:23Z","#,
+            r#""purposes":{"1":{"id":1,"name":"Store and/or access information on a device","description":"Cookies","#,
+            r#""descriptionLegal":"Vendors can: Store and access information on the device"}},"#,
+            r#""specialPurposes":{"1":{"id":1,"name":"Ensure security, prevent fraud, and debug","description":"Security","#,
+            r#""descriptionLegal":"To ensure security"}},"features":{},"specialFeatures":{},"#,
+            r#""stacks":{"1":{"id":1,"purposes":[],"specialFeatures":[1,2],"name":"Precise geolocation data","#,
+            r#""description":"Precise geolocation data"}},"#,
+            r#""vendors":{"8":{"id":8,"name":"Emerse Sverige AB","purposes":[1,3,4],"legIntPurposes":[2,7],"#,
+            r#""flexiblePurposes":[2],"specialPurposes":[1,2],"features":[],"specialFeatures":[],"#,
+            r#""policyUrl":"https://www.emerse.com/privacy-policy/","usesNonCookieAccess":false,"#,
+            r#""cookieMaxAgeSeconds":31536000,"cookieRefresh":false,"usesCookies":true},"#,
+            r#""32":{"id":32,"name":"Xandr, Inc.","purposes":[1],"legIntPurposes":[],"flexiblePurposes":[],"#,
+            r#""specialPurposes":[],"features":[2,3],"specialFeatures":[],"#,
+            r#""policyUrl":"https://www.xandr.com/privacy/platform-privacy-policy/","#,
+            r#""deletedDate":"2020-06-11T00:00:00Z","overflow":{"httpGetLimit":32}}}}"#
+        );
+        let vendor_list: VendorList = v2.parse().unwrap();
+        assert_eq!(vendor_list.to_string().unwrap(), v2);
+
+        // the IAB orders unmodeled fields differently, but the JSON is equivalent
+        let published = v2.replace(
+            r#""usesNonCookieAccess":false,"cookieMaxAgeSeconds":31536000,"cookieRefresh":false,"usesCookies":true"#,
+            r#""cookieMaxAgeSeconds":31536000,"usesCookies":true,"cookieRefresh":false,"usesNonCookieAccess":false"#,
+        );
+        let reserialized = published.parse::<VendorList>().unwrap().to_string().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&reserialized).unwrap(),
+            serde_json::from_str::<Value>(&published).unwrap()
+        );
+    }
 
     #[test]
     fn deserialize_good() {}
+
+    #[test]
+    fn deserialize_v2_good() {
+        let vendor_list: VendorList = r#"{
+            "gvlSpecificationVersion": 2,
+            "vendorListVersion": 48,
+            "tcfPolicyVersion": 2,
+            "lastUpdated": "2020-07-02T16:00:23Z",
+            "purposes": {
+                "1": {
+                    "id": 1,
+                    "name": "Store and/or access information on a device",
+                    "description": "Cookies, device identifiers, or other information can be stored or accessed on your device for the purposes presented to you.",
+                    "descriptionLegal": "Vendors can: Store and access information on the device such as cookies and device identifiers presented to a user."
+                }
+            },
+            "features": {},
+            "specialFeatures": {
+                "1": {
+                    "id": 1,
+                    "name": "Use precise geolocation data",
+                    "description": "Your precise geolocation data can be used in support of one or more purposes.",
+                    "descriptionLegal": "Vendors can: Collect and process precise geolocation data in support of one or more purposes."
+                }
+            },
+            "vendors": {
+                "32": {
+                    "id": 32,
+                    "name": "Xandr, Inc.",
+                    "purposes": [1, 2, 3, 4],
+                    "legIntPurposes": [7, 9, 10],
+                    "flexiblePurposes": [2, 7, 9, 10],
+                    "specialPurposes": [1, 2],
+                    "features": [2, 3],
+                    "specialFeatures": [1],
+                    "policyUrl": "https://www.xandr.com/privacy/platform-privacy-policy/"
+                }
+            }
+        }"#.parse().unwrap();
+
+        assert!(vendor_list.is_v2());
+        assert_eq!(vendor_list.tcf_policy_version, Some(2));
+        assert_eq!(vendor_list.purposes[&1].id, PurposeId(1));
+
+        let vendor = &vendor_list.vendors[&32];
+        assert_eq!(
+            vendor.purpose_ids,
+            vec![PurposeId(1), PurposeId(2), PurposeId(3), PurposeId(4)]
+        );
+        assert_eq!(
+            vendor.leg_int_purpose_ids,
+            vec![PurposeId(7), PurposeId(9), PurposeId(10)]
+        );
+        assert_eq!(vendor.feature_ids, vec![FeatureId(2), FeatureId(3)]);
+        assert_eq!(vendor.special_purpose_ids, vec![PurposeId(1), PurposeId(2)]);
+        assert_eq!(vendor.special_feature_ids, vec![FeatureId(1)]);
+        assert!(vendor_list.special_purposes.is_empty());
+        assert_eq!(vendor_list.special_features[&1].name, "Use precise geolocation data");
+
+        let v1: VendorList = VENDOR_LIST.parse().unwrap();
+        assert!(!v1.is_v2());
+    }
+
+    #[test]
+    fn legal_basis_decision_table() {
+        use self::LegalBasis::*;
+
+        let (forbid, require_consent, require_li) = (
+            Some(RestrictionType::NotAllowed),
+            Some(RestrictionType::RequireConsent),
+            Some(RestrictionType::RequireLegitimateInterest),
+        );
+
+        // (declared, flexible, restriction, legal basis)
+        let table = [
+            (None, false, None, NotAllowed),
+            (None, false, forbid, NotAllowed),
+            (None, false, require_consent, NotAllowed),
+            (None, false, require_li, NotAllowed),
+            (Some(Consent), false, None, Consent),
+            (Some(Consent), false, forbid, NotAllowed),
+            (Some(Consent), false, require_consent, Consent),
+            (Some(Consent), false, require_li, NotAllowed),
+            (Some(Consent), true, None, Consent),
+            (Some(Consent), true, forbid, NotAllowed),
+            (Some(Consent), true, require_consent, Consent),
+            (Some(Consent), true, require_li, LegitimateInterest),
+            (Some(LegitimateInterest), false, None, LegitimateInterest),
+            (Some(LegitimateInterest), false, forbid, NotAllowed),
+            (Some(LegitimateInterest), false, require_consent, NotAllowed),
+            (Some(LegitimateInterest), false, require_li, LegitimateInterest),
+            (Some(LegitimateInterest), true, None, LegitimateInterest),
+            (Some(LegitimateInterest), true, forbid, NotAllowed),
+            (Some(LegitimateInterest), true, require_consent, Consent),
+            (Some(LegitimateInterest), true, require_li, LegitimateInterest),
+        ];
+
+        for &(declared, flexible, restriction, expected) in table.iter() {
+            for &purpose_id in &[PurposeId(1), PurposeId(2)] {
+                let ids = |b: bool| if b { vec![purpose_id] } else { vec![] };
+                let vendor = Vendor {
+                    id: VendorId(32),
+                    name: "Vendor".to_string(),
+                    policy_url: "https://example.com/privacy".to_string(),
+                    purpose_ids: ids(declared == Some(Consent)),
+                    leg_int_purpose_ids: ids(declared == Some(LegitimateInterest)),
+                    flexible_purpose_ids: ids(flexible),
+                    feature_ids: vec![],
+                    special_purpose_ids: vec![],
+                    special_feature_ids: vec![],
+                    uses_non_cookie_access: None,
+                    deleted_date: None,
+                    overflow: None,
+                    extra: Map::new(),
+                };
+
+                // restrictions on other purposes and vendors are ignored
+                let mut restrictions = vec![
+                    PublisherRestriction {
+                        purpose_id: PurposeId(3),
+                        restriction_type: RestrictionType::NotAllowed,
+                        vendors: [VendorId(32)].iter().cloned().collect(),
+                    },
+                    PublisherRestriction {
+                        purpose_id,
+                        restriction_type: RestrictionType::NotAllowed,
+                        vendors: [VendorId(31)].iter().cloned().collect(),
+                    },
+                ];
+                restrictions.extend(restriction.map(|restriction_type| PublisherRestriction {
+                    purpose_id,
+                    restriction_type,
+                    vendors: [VendorId(32)].iter().cloned().collect(),
+                }));
+
+                // purpose 1 can never rely on legitimate interest
+                let expected = match (purpose_id, expected) {
+                    (PurposeId(1), LegitimateInterest) => NotAllowed,
+                    _ => expected,
+                };
+                assert_eq!(
+                    vendor.legal_basis(purpose_id, &restrictions),
+                    expected,
+                    "purpose {}, declared {:?}, flexible {}, restriction {:?}",
+                    purpose_id,
+                    declared,
+                    flexible,
+                    restriction
+                );
+            }
+        }
+
+        // contradictory restrictions
+        let vendor = Vendor {
+            purpose_ids: vec![PurposeId(2)],
+            flexible_purpose_ids: vec![PurposeId(2)],
+            ..VENDOR_LIST.parse::<VendorList>().unwrap().vendors.remove(&32).unwrap()
+        };
+        let restrictions: Vec<PublisherRestriction> = [
+            RestrictionType::RequireLegitimateInterest,
+            RestrictionType::RequireConsent,
+        ]
+        .iter()
+        .map(|&restriction_type| PublisherRestriction {
+            purpose_id: PurposeId(2),
+            restriction_type,
+            vendors: [VendorId(32)].iter().cloned().collect(),
+        })
+        .collect();
+        assert_eq!(vendor.legal_basis(2, &restrictions[..1]), LegitimateInterest);
+        assert_eq!(vendor.legal_basis(2, &restrictions), NotAllowed);
+    }
+
+    #[test]
+    fn group_by_stacks() {
+        let vendor_list: VendorList = r#"{
+            "gvlSpecificationVersion": 2,
+            "vendorListVersion": 48,
+            "tcfPolicyVersion": 2,
+            "lastUpdated": "2020-07-02T16:00:23Z",
+            "purposes": {},
+            "features": {},
+            "vendors": {},
+            "stacks": {
+                "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Precise geolocation", "description": "" },
+                "2": { "id": 2, "purposes": [1, 2, 3, 4], "specialFeatures": [], "name": "Two", "description": "" },
+                "3": { "id": 3, "purposes": [1, 3, 5], "specialFeatures": [], "name": "Three", "description": "" },
+                "4": { "id": 4, "purposes": [2, 4, 6], "specialFeatures": [], "name": "Four", "description": "" },
+                "5": { "id": 5, "purposes": [1, 8], "specialFeatures": [], "name": "Five", "description": "" }
+            }
+        }"#.parse().unwrap();
+
+        let purposes: PurposeSet = (1..=6).chain(Some(10)).map(PurposeId).collect();
+        let special_features: FeatureSet = [FeatureId(1)].iter().cloned().collect();
+
+        let covered: Vec<StackId> = vendor_list
+            .stacks_covered_by(&purposes, &special_features)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(covered, vec![StackId(1), StackId(2), StackId(3), StackId(4)]);
+
+        // taking the largest stack first would need stacks 2, 3 and 4
+        assert_eq!(
+            vendor_list.group_by_stacks(&purposes, &special_features),
+            StackGrouping {
+                stacks: vec![StackId(1), StackId(3), StackId(4)],
+                purposes: vec![PurposeId(10)],
+                special_features: vec![],
+            }
+        );
+
+        let consent = "BOEFEAyOEFEAyAHABDENAI4AAAB9vABAASA".parse().unwrap();
+        let grouping = vendor_list.group_consent_by_stacks(&consent);
+        assert!(grouping.stacks.is_empty());
+        assert_eq!(grouping.purposes, vec![PurposeId(1), PurposeId(2), PurposeId(3)]);
+
+        let v1: VendorList = VENDOR_LIST.parse().unwrap();
+        assert!(v1.stacks.is_empty());
+        assert!(!v1.to_string().unwrap().contains("stacks"));
+    }
+
+    #[test]
+    fn deleted_vendors() {
+        let vendor_list: VendorList = r#"{
+            "vendorListVersion": 8,
+            "lastUpdated": "2018-05-28T16:00:15Z",
+            "purposes": [],
+            "features": [],
+            "vendors": [
+                { "id": 1, "name": "Active", "policyUrl": "", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] },
+                { "id": 2, "name": "Deleted", "policyUrl": "", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [], "deletedDate": "2018-05-01T00:00:00Z" },
+                { "id": 3, "name": "Overflow", "policyUrl": "", "purposeIds": [], "legIntPurposeIds": [], "featureIds": [], "overflow": { "httpGetLimit": 128 } }
+            ]
+        }"#.parse().unwrap();
+
+        let active = |now: &str| -> Vec<u16> {
+            let now = now.parse().unwrap();
+            vendor_list.active_vendors_at(now).iter().map(|v| v.id.0).collect()
+        };
+        assert_eq!(active("2018-04-30T23:59:59Z"), vec![1, 2, 3]);
+        assert_eq!(active("2018-05-01T00:00:00Z"), vec![1, 3]);
+
+        let deleted = &vendor_list.vendors[&2];
+        assert!(deleted.is_active_at("2018-04-30T23:59:59Z".parse().unwrap()));
+        assert!(!deleted.is_active_at("2018-05-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(vendor_list.vendors[&3].overflow, Some(Overflow { http_get_limit: 128 }));
+        assert_eq!(vendor_list.vendors[&3].uses_non_cookie_access, None);
+    }
+
+    #[test]
+    fn vendor_queries() {
+        let mut vendor_list: VendorList = r#"{
+            "vendorListVersion": 8,
+            "lastUpdated": "2018-05-28T16:00:15Z",
+            "purposes": [],
+            "features": [],
+            "vendors": [
+                { "id": 32, "name": "AppNexus Inc.", "policyUrl": "", "purposeIds": [1], "legIntPurposeIds": [3], "featureIds": [2, 3] },
+                { "id": 2, "name": "appier", "policyUrl": "", "purposeIds": [1, 3], "legIntPurposeIds": [], "featureIds": [] },
+                { "id": 7, "name": "Applovin", "policyUrl": "", "purposeIds": [], "legIntPurposeIds": [3], "featureIds": [2] },
+                { "id": 9, "name": "Criteo", "policyUrl": "", "purposeIds": [1], "legIntPurposeIds": [], "featureIds": [] }
+            ]
+        }"#.parse().unwrap();
+
+        let ids = |vendors: Vec<&Vendor>| vendors.iter().map(|v| v.id.0).collect::<Vec<_>>();
+        assert_eq!(ids(vendor_list.vendors_with_purpose(1)), vec![2, 9, 32]);
+        assert_eq!(ids(vendor_list.vendors_with_leg_int_purpose(3)), vec![7, 32]);
+        assert_eq!(ids(vendor_list.vendors_with_feature(2)), vec![7, 32]);
+        assert!(vendor_list.vendors_with_feature(1).is_empty());
+        assert!(vendor_list.vendors_with_special_feature(1).is_empty());
+
+        assert_eq!(ids(vendor_list.vendors_by_name_prefix("App")), vec![2, 7, 32]);
+        assert_eq!(ids(vendor_list.vendors_by_name_prefix("appn")), vec![32]);
+        assert!(vendor_list.vendors_by_name_prefix("Z").is_empty());
+        assert_eq!(vendor_list.vendors_by_name_prefix("").len(), 4);
+
+        vendor_list.vendors.remove(&VendorId(9));
+        vendor_list.vendors.get_mut(&VendorId(2)).unwrap().purpose_ids.clear();
+        vendor_list.reindex();
+        assert_eq!(ids(vendor_list.vendors_with_purpose(1)), vec![32]);
+
+        // deserialized other than through from_str, e.g. embedded in another document
+        let embedded: Value = json!({ "vendorList": vendor_list });
+        let vendor_list: VendorList = serde_json::from_value(embedded["vendorList"].clone()).unwrap();
+        assert_eq!(ids(vendor_list.vendors_with_purpose(1)), vec![32]);
+        assert_eq!(ids(vendor_list.vendors_by_name_prefix("appn")), vec![32]);
+    }
+
+    #[test]
+    fn translations_good() {
+        let mut vendor_list: VendorList = VENDOR_LIST.parse().unwrap();
+        let translations = r#"{
+            "purposes": [
+                { "id": 1, "name": "Stockage et accès aux informations", "description": "Stockage" }
+            ],
+            "features": []
+        }"#.parse().unwrap();
+        vendor_list.add_translations("fr", translations).unwrap();
+
+        assert_eq!(vendor_list.purpose_name(1, "fr"), Some("Stockage et accès aux informations"));
+        assert_eq!(vendor_list.purpose_name(1, "FR"), Some("Stockage et accès aux informations"));
+        assert_eq!(vendor_list.purpose_name(2, "fr"), Some("Personalisation"));
+        assert_eq!(vendor_list.purpose_name(1, "de"), Some("Information storage and access"));
+        assert_eq!(vendor_list.feature_name(1, "fr"), Some("Offline data matching"));
+        assert_eq!(vendor_list.purpose_name(3, "fr"), None);
+    }
+
+    #[test]
+    fn translations_unknown_id() {
+        let mut vendor_list: VendorList = VENDOR_LIST.parse().unwrap();
+        let translations = r#"{
+            "purposes": [{ "id": 7, "name": "Inconnu", "description": "Inconnu" }]
+        }"#.parse().unwrap();
+
+        assert!(vendor_list.add_translations("fr", translations).is_err());
+        assert!(vendor_list.translations.is_empty());
+    }
+
+    #[test]
+    fn translations_v2() {
+        let mut vendor_list: VendorList = r#"{
+            "gvlSpecificationVersion": 2,
+            "vendorListVersion": 48,
+            "tcfPolicyVersion": 2,
+            "lastUpdated": "2020-07-02T16:00:23Z",
+            "purposes": {},
+            "specialPurposes": {
+                "1": { "id": 1, "name": "Ensure security, prevent fraud, and debug", "description": "Security" },
+                "2": { "id": 2, "name": "Technically deliver ads or content", "description": "Delivery" }
+            },
+            "features": {},
+            "specialFeatures": {
+                "1": { "id": 1, "name": "Use precise geolocation data", "description": "Geolocation" }
+            },
+            "stacks": {
+                "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Precise geolocation", "description": "Geolocation" }
+            },
+            "vendors": {}
+        }"#.parse().unwrap();
+        let translations = r#"{
+            "purposes": {},
+            "specialPurposes": {
+                "1": { "id": 1, "name": "Assurer la sécurité", "description": "Sécurité" }
+            },
+            "features": {},
+            "specialFeatures": {
+                "1": { "id": 1, "name": "Utiliser des données de géolocalisation précises", "description": "Géolocalisation" }
+            },
+            "stacks": {
+                "1": { "id": 1, "purposes": [], "specialFeatures": [1], "name": "Géolocalisation précise", "description": "Géolocalisation" }
+            }
+        }"#.parse().unwrap();
+        vendor_list.add_translations("fr", translations).unwrap();
+
+        assert_eq!(vendor_list.special_purpose_name(1, "fr"), Some("Assurer la sécurité"));
+        assert_eq!(vendor_list.special_purpose_description(1, "fr"), Some("Sécurité"));
+        assert_eq!(
+            vendor_list.special_purpose_name(2, "fr"),
+            Some("Technically deliver ads or content")
+        );
+        assert_eq!(
+            vendor_list.special_feature_name(1, "FR"),
+            Some("Utiliser des données de géolocalisation précises")
+        );
+        assert_eq!(vendor_list.special_feature_description(1, "de"), Some("Geolocation"));
+        assert_eq!(vendor_list.stack_name(1, "fr"), Some("Géolocalisation précise"));
+        assert_eq!(vendor_list.stack_description(1, "fr"), Some("Géolocalisation"));
+        assert_eq!(vendor_list.stack_name(2, "fr"), None);
+
+        let unknown_stack = r#"{
+            "stacks": {
+                "2": { "id": 2, "purposes": [1], "specialFeatures": [], "name": "Inconnu", "description": "Inconnu" }
+            }
+        }"#.parse().unwrap();
+        assert!(vendor_list.add_translations("de", unknown_stack).is_err());
+        let unknown_special_purpose = r#"{
+            "specialPurposes": [{ "id": 3, "name": "Inconnu", "description": "Inconnu" }]
+        }"#.parse().unwrap();
+        assert!(vendor_list.add_translations("de", unknown_special_purpose).is_err());
+        assert!(!vendor_list.translations.contains_key("de"));
+    }
 }